@@ -0,0 +1,21 @@
+use std::env;
+use std::process::Command;
+
+// `Span::local_file` (used to resolve paths relative to the file calling the macros) is only
+// available since rust 1.88.
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(doc_comment_local_file)");
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned());
+    let version = match Command::new(rustc).arg("--version").output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).into_owned(),
+        Err(_) => return,
+    };
+    // The output looks like "rustc 1.88.0 (6b00bc388 2025-06-23)".
+    let minor = version
+        .split(|c: char| c == ' ' || c == '.')
+        .nth(2)
+        .and_then(|minor| minor.parse::<u32>().ok());
+    if minor.map(|minor| minor >= 88) == Some(true) {
+        println!("cargo:rustc-cfg=doc_comment_local_file");
+    }
+}
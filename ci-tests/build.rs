@@ -0,0 +1,101 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// Cargo only gives the enabled features to build scripts, so they're forwarded to the compiler
+// for `require_feature`.
+fn main() {
+    if env::var_os("CARGO_FEATURE_GATED").is_some() {
+        println!("cargo:rustc-env=CARGO_FEATURE_GATED=1");
+    }
+    // The absolute path of a fixture can only be known here, so the `doctest!` calls using it are
+    // generated and included in `lib.rs`.
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let fixture = Path::new(&manifest_dir)
+        .join("..")
+        .join("tests")
+        .join("fixtures")
+        .join("with spaces")
+        .join("guide.md");
+    let fixture = fixture.to_str().unwrap();
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("absolute.rs");
+    fs::write(
+        out,
+        format!(
+            "doc_comment::doctest!({:?}, absolute_spaces);\n\
+             doc_comment::doctest!({:?}, absolute_spaces_native, native);\n",
+            fixture, fixture
+        ),
+    )
+    .unwrap();
+    write_escaping_corpus(&Path::new(&env::var("OUT_DIR").unwrap()).join("escaping.rs"));
+}
+
+// Same as `escape_content` in `doc-comment`.
+fn escape_content(s: &str) -> String {
+    s.replace("\\", "\\\\")
+        .replace("\"", "\\\"")
+        .replace("\r", "\\r")
+}
+
+// The strings are written escaped like `doc-comment` does and through `Debug`, so rustc checks
+// that both give back the same strings.
+fn write_escaping_corpus(out: &Path) {
+    let mut corpus = [
+        "",
+        "\"",
+        "\\",
+        "\\\"",
+        "\"\\",
+        "a \"quoted\" string",
+        "C:\\path\\to\\file",
+        "line\nother line\n",
+        "windows\r\nline endings\r\n",
+        "bare\rcarriage return",
+        "\ttabs\t",
+        "r#\"raw string\"#",
+        "unicode: é à ç ß 漢字",
+        "emoji: 🦀🎉",
+        "```rust\nlet x = \"\\n\";\n```\n",
+        "{} {{}} {:?}",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect::<Vec<_>>();
+    const ALPHABET: &[char] = &[
+        'a', 'Z', '0', ' ', '"', '\\', '\'', '\n', '\r', '\t', '`', '#', '{', '}', 'é', '🦀',
+    ];
+    // A simple xorshift so the corpus is the same on every build.
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    for _ in 0..500 {
+        let len = (next() % 32) as usize;
+        corpus.push(
+            (0..len)
+                .map(|_| ALPHABET[(next() % ALPHABET.len() as u64) as usize])
+                .collect(),
+        );
+    }
+    let escaped = corpus
+        .iter()
+        .map(|s| format!("\"{}\"", escape_content(s)))
+        .collect::<Vec<_>>();
+    let original = corpus
+        .iter()
+        .map(|s| format!("{:?}", s))
+        .collect::<Vec<_>>();
+    fs::write(
+        out,
+        format!(
+            "pub const ESCAPED: &[&str] = &[{}];\npub const ORIGINAL: &[&str] = &[{}];\n",
+            escaped.join(", "),
+            original.join(", ")
+        ),
+    )
+    .unwrap();
+}
@@ -2,4 +2,1354 @@
 extern {}
 
 // We can use parenthesis too, but not in rustc 1.44.1...
-doc_comment::doctest!{ "../README.md" }
\ No newline at end of file
+doc_comment::doctest!{ "../README.md" }
+doc_comment::doctest!{ "../tests/fixtures/tabs.md", tabs, tab_width = 4 }
+doc_comment::doctest!{ "../tests/fixtures/skip.md", skip, skip_containing = ["panic!", "todo!"] }
+// The option values are unescaped (and can be raw strings) like any string literal.
+doc_comment::doctest!{
+    "../tests/fixtures/skip.md",
+    skip_escaped,
+    skip_containing = ["(\"something went wrong\")", r"todo!"],
+}
+doc_comment::doctest!{ "../README.md", readme_native, native }
+
+#[doc = doc_comment::doctest!("../tests/fixtures/tabs.md", attach, tab_width = 4)]
+pub struct Attached;
+
+/// The test name given to `doctest!` must be a valid identifier:
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../README.md", my-mod);
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../README.md", 2fast);
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../README.md", match);
+/// ```
+pub mod invalid_names {}
+
+doc_comment::doctest!{ "../tests/fixtures/latin1.md", latin1, encoding = "latin1" }
+
+/// Both spellings of the path are registered:
+///
+/// ```
+/// assert_eq!(
+///     test_ci::registered::__DOCTEST_FILE_TESTS_FIXTURES_TABS_MD_113AA126,
+///     "../tests/fixtures/tabs.md",
+/// );
+/// assert_eq!(
+///     test_ci::registered::__DOCTEST_FILE_TESTS_FIXTURES_TABS_MD_D6EC4757,
+///     "./../tests/fixtures/tabs.md",
+/// );
+/// ```
+pub mod registered {
+    doc_comment::doctest!{ "../tests/fixtures/tabs.md", tabs_registered, tab_width = 4, register }
+    doc_comment::doctest!{ "./../tests/fixtures/tabs.md", tabs_registered_again, tab_width = 4, register }
+}
+
+doc_comment::doctest!{ "../tests/fixtures/escaping.md", escaping }
+
+/// The strings escaped like `doc-comment` does are read back unchanged by rustc:
+///
+/// ```
+/// use test_ci::escaped_strings::{ESCAPED, ORIGINAL};
+///
+/// assert_eq!(ESCAPED, ORIGINAL);
+/// ```
+pub mod escaped_strings {
+    include!(concat!(env!("OUT_DIR"), "/escaping.rs"));
+}
+doc_comment::doctest!{ "../tests/fixtures/[draft].md", draft }
+// Paths are relative to this file, not to the `src` directory of `doc-comment`.
+doc_comment::doctest!{ "local.md", local }
+macro_rules! identity {
+    ($content:literal) => {
+        $content
+    };
+}
+// The content goes through the given macro before being put in `#[doc]`.
+doc_comment::doctest!{ "../tests/fixtures/body.md", body_via, via = identity }
+doc_comment::doctest!{ "../tests/fixtures/body.md", body_via_path, via = ::std::concat }
+
+// Generates `test_ci_body` and `test_ci_named` since the package is `test-ci`.
+doc_comment::doctest!{ "../tests/fixtures/body.md", crate_suffix }
+doc_comment::doctest!{ "../tests/fixtures/body.md", named, crate_suffix }
+#[allow(unused_imports)]
+use test_ci_body as _;
+#[allow(unused_imports)]
+use test_ci_named as _;
+
+// The literals and the files are concatenated.
+doc_comment::doctest!{
+    lit = "Overview\n========\n\n",
+    "../tests/fixtures/body.md",
+    lit = "\n```rust\nassert_eq!(1 + 1, 2);\n```\n",
+    "../tests/fixtures/footer.md",
+    mixed,
+    expose_clean = MIXED,
+}
+
+/// ```
+/// assert!(test_ci::MIXED.starts_with("Overview\n========\n\n## Body\n"));
+/// assert!(test_ci::MIXED.contains("```\n\n```rust\nassert_eq!(1 + 1, 2);\n```\n"));
+/// ```
+pub mod mixed_content {}
+
+// Only the "Current" section is tested.
+doc_comment::doctest!{
+    "../tests/fixtures/reference.md",
+    reference,
+    ranges_from = "../tests/fixtures/reference.md.lines",
+}
+doc_comment::doctest!{ "../tests/fixtures/body.md", body_markers, source_markers, expose_clean = CLEAN_BODY }
+doc_comment::doctest!{ "../tests/fixtures/body.md", body_fn, holder = "fn" }
+doc_comment::doctest!{ "../tests/fixtures/body.md", body_struct, holder = "struct" }
+doc_comment::doctest!{ "../tests/fixtures/body.md", holder = "extern" }
+doc_comment::doctest!{
+    "../tests/fixtures/front_matter.md",
+    front_matter,
+    strip_front_matter,
+    preserve_lines,
+}
+// The second panicking code block isn't tested since it's the same as the first one.
+doc_comment::doctest!{ "../tests/fixtures/duplicates.md", duplicates, dedup_blocks }
+// Generates `block_1`, `block_2` and `block_3`.
+doc_comment::doctest!{ "../tests/fixtures/split.md", split }
+doc_comment::doctest!{ "../tests/fixtures/split.md", split_stable, split, stable_names }
+
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../tests/fixtures/split.md", stable_names);
+/// ```
+pub mod stable_names {}
+doc_comment::doctest!{ "../tests/fixtures/carriage_return.md", carriage_return }
+// All the paths are unescaped like the main one (`\x2e` is `.`).
+doc_comment::doctest!{
+    r"../tests/fixtures/body.md",
+    wrapped_escaped,
+    prepend = "\x2e./tests/fixtures/header.md",
+    append = r"../tests/fixtures/footer.md",
+    must_match = "\x2e./tests/fixtures/copy/body.md",
+}
+doc_comment::doctest!{ from_rust = "\x2e./tests/fixtures/demo.rs", demo_escaped }
+doc_comment::doctest!{ from_rust_all = "\x2e./tests/fixtures/items.rs", items_escaped }
+doc_comment::doctest!{
+    data = "\x2e./tests/fixtures/options.toml",
+    template = "\x2e./tests/fixtures/options.md.tmpl",
+    options_escaped,
+}
+doc_comment::doctest!{
+    "../tests/fixtures/body.md",
+    wrapped,
+    prepend = "../tests/fixtures/header.md",
+    append = "../tests/fixtures/footer.md",
+}
+doc_comment::doctest!{ from_rust = "../tests/fixtures/demo.rs", demo }
+// Generates `items_increment` and `items_wrapper`.
+doc_comment::doctest!{ from_rust_all = "../tests/fixtures/items.rs" }
+doc_comment::doctest!{
+    data = "../tests/fixtures/options.toml",
+    template = "../tests/fixtures/options.md.tmpl",
+    options_toml,
+}
+doc_comment::doctest!{
+    data = "../tests/fixtures/options.json",
+    template = "../tests/fixtures/options.md.tmpl",
+    options_json,
+}
+
+/// All the keys used by the template must be in the data file:
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!(
+///     data = "../tests/fixtures/options.toml",
+///     template = "../tests/fixtures/missing_key.md.tmpl",
+/// );
+/// ```
+pub mod template_missing_key {}
+doc_comment::doctest!{
+    "../tests/fixtures/question_mark.md",
+    question_mark,
+    main_returns = "Result<(), std::num::ParseIntError>",
+}
+// `Ok(())` isn't added since `main` doesn't return a `Result`.
+doc_comment::doctest!{
+    "../tests/fixtures/exit_code.md",
+    exit_code,
+    main_returns = "std::process::ExitCode",
+}
+
+/// Errors are reported with `compile_error!` instead of panicking:
+///
+/// ```compile_fail,edition2018
+/// #[doc_comment::doc_comment(concat!("a", "b"))]
+/// pub fn unsupported_macro() {}
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../README.md", unknown_option = 1);
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../README.md", tab_width = "4");
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../tests/fixtures/missing.md");
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../tests/fixtures");
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../tests/fixtures", native);
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../README.md", debug_path);
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../README.md", ranges_from = "../tests/fixtures/malformed.lines");
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../README.md", holder = "fn");
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../README.md", readme, holder = "extern");
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../README.md", readme, holder = "enum");
+/// ```
+///
+/// A suffixed literal isn't a file path, it's rejected with "Invalid string literal":
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../README.md"x, native);
+/// ```
+pub mod errors {}
+
+// Only the `no_run` variant is generated because the examples can't run.
+doc_comment::doctest!{ "../tests/fixtures/network.md", [no_run => network_no_run] }
+doc_comment::doctest!{
+    "../tests/fixtures/panics.md",
+    [should_panic => panics_should_panic],
+    [no_run => panics_no_run],
+}
+doc_comment::doctest!{ "../tests/fixtures/panics.md", panics_attrs, block_attrs = "should_panic" }
+doc_comment::doctest!{
+    "../tests/fixtures/modifiers.md",
+    modifiers,
+    add_modifier = "no_run",
+    to = "should_panic",
+}
+
+// The `gated` feature is enabled (and forwarded by `build.rs`), `missing` isn't so `panics.md` is
+// not tested a third time.
+doc_comment::doctest!{ "../tests/fixtures/gated.md", gated, require_feature = "gated" }
+doc_comment::doctest!{ "../tests/fixtures/panics.md", missing, require_feature = "missing" }
+
+/// Shell prompts in rust code blocks are detected with `lint_prompts`:
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../tests/fixtures/prompt.md", lint_prompts);
+/// ```
+pub mod lint_prompts {}
+
+/// Unexpanded templates in rust code blocks are detected with `lint_templates`:
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../tests/fixtures/template.md", lint_templates = ["{{", "${"]);
+/// ```
+pub mod lint_templates {}
+// There is no `{{` in the rust code blocks, so nothing is reported.
+doc_comment::doctest!{ "../tests/fixtures/template.md", template, lint_templates = "{{" }
+doc_comment::doctest!{ "../README.md", readme_lint_prompts, lint_prompts }
+
+doc_comment::doctest!{ "../tests/fixtures/antipatterns.md", antipatterns, expect_all_fail }
+
+/// All the rust code blocks must be `compile_fail` with `expect_all_fail`:
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../tests/fixtures/antipatterns_stray.md", expect_all_fail);
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../tests/fixtures/skip.md", expect_all_fail, skip_containing = "");
+/// ```
+pub mod expect_all_fail {}
+
+/// Code blocks which won't be tested are reported with `report_skipped` (as a deprecation
+/// warning) and `strict`:
+///
+/// ```compile_fail,edition2018
+/// #![deny(deprecated)]
+/// doc_comment::doctest!("../tests/fixtures/hidden.md", report_skipped);
+/// # fn main() {}
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!(
+///     "../tests/fixtures/skip.md",
+///     skip_containing = ["panic!", "todo!"],
+///     strict,
+/// );
+/// # fn main() {}
+/// ```
+pub mod report_skipped {}
+
+doc_comment::doctest!{ "../tests/fixtures/modes.md", modes, expose_clean = CLEAN_MODES }
+
+/// What rustdoc does with the code blocks is reported with `print_modes` (as a deprecation
+/// warning, or an error with `strict`), the file is still tested:
+///
+/// ```compile_fail,edition2018
+/// #![deny(deprecated)]
+/// doc_comment::doctest!("../tests/fixtures/modes.md", print_modes);
+/// # fn main() {}
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../tests/fixtures/tabs.md", print_modes, strict);
+/// # fn main() {}
+/// ```
+///
+/// ```edition2018
+/// #![allow(deprecated)]
+/// doc_comment::doctest!("../tests/fixtures/modes.md", print_modes);
+/// # fn main() {}
+/// ```
+pub mod print_modes {}
+
+pub const MODES_DOC: &str = doc_comment::doctest!("../tests/fixtures/modes.md", attach);
+
+/// The clean copy only keeps the language of the rust code blocks, the tested documentation keeps
+/// their modifiers:
+///
+/// ```
+/// assert!(test_ci::MODES_DOC.contains("```no_run\nloop {}"));
+/// assert!(test_ci::MODES_DOC.contains("```rust,should_panic\n"));
+/// assert!(test_ci::CLEAN_MODES.contains("```rust\nloop {}"));
+/// assert!(test_ci::CLEAN_MODES.contains("```rust\npanic!"));
+/// assert!(test_ci::CLEAN_MODES.contains("```\nassert_eq!(1 + 1, 2);"));
+/// assert!(test_ci::CLEAN_MODES.contains("```text\nplain text"));
+/// assert!(!test_ci::CLEAN_MODES.contains("no_run"));
+/// assert!(!test_ci::CLEAN_MODES.contains("should_panic"));
+/// assert!(!test_ci::CLEAN_MODES.contains("compile_fail"));
+/// ```
+pub mod clean_modifiers {}
+
+doc_comment::doctest!{
+    "../README.md",
+    readme_crate_name,
+    expect_crate = "doc_comment",
+    forbidden_crates = ["doc_comments", "old_crate"],
+}
+
+/// Examples using an old crate name are errors and not using the expected one is a (deprecation)
+/// warning:
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!(
+///     "../tests/fixtures/renamed.md",
+///     expect_crate = "new_crate",
+///     forbidden_crates = ["old_crate"],
+/// );
+/// # fn main() {}
+/// ```
+///
+/// ```compile_fail,edition2018
+/// #![deny(deprecated)]
+/// doc_comment::doctest!("../tests/fixtures/body.md", expect_crate = "new_crate");
+/// # fn main() {}
+/// ```
+pub mod crate_names {}
+// Nothing is reported when all the code blocks are tested (`clippy` is run with `-D warnings`).
+doc_comment::doctest!{ "../tests/fixtures/tabs.md", tabs_report_skipped, tab_width = 4, report_skipped }
+// The code spans of the table aren't code blocks, so there is nothing to report.
+doc_comment::doctest!{ "../tests/fixtures/tables.md", tables, strict }
+
+doc_comment::doctest!{ base_dir = "../tests/fixtures", files = ["escaping.md", "body.md"], group }
+doc_comment::doctest!{ base_dir = "../tests/fixtures", files = ["escaping.md", "body.md"], sorted_group, order = "sorted" }
+
+/// `base_dir` must be a directory:
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!(base_dir = "../README.md", files = ["tabs.md"]);
+/// ```
+pub mod base_dir_not_a_directory {}
+
+// `panics.md` is 65 bytes: it's skipped in the first case (it would fail otherwise) and tested in
+// the second one.
+doc_comment::doctest!{
+    base_dir = "../tests/fixtures",
+    files = ["body.md", "panics.md"],
+    sized,
+    max_file_size = 64,
+    on_oversize = "skip",
+}
+doc_comment::doctest!{
+    "../tests/fixtures/panics.md",
+    panics_max_size,
+    max_file_size = 65,
+    block_attrs = "should_panic",
+}
+
+/// Files bigger than `max_file_size` are errors by default:
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../tests/fixtures/panics.md", max_file_size = 64);
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../tests/fixtures/panics.md", on_oversize = "skip");
+/// ```
+pub mod max_file_size {}
+
+// Generates the `guide`, `guide_fr` and `guide_de` modules.
+doc_comment::doctest!{ base_dir = "../tests/fixtures", base = "guide", locales = ["", "fr", "de"], ext = "md" }
+
+doc_comment::doctest_all! {
+    in all_docs;
+    "../tests/fixtures/escaping.md" => escaping,
+    "../tests/fixtures/[draft].md" => draft,
+    "\x2e./tests/fixtures/body.md" => body,
+}
+
+/// `doctest_all` generates one module containing a module per file:
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest_all! { in docs_tests; "../tests/fixtures/escaping.md" }
+/// # fn main() {}
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest_all! { "../tests/fixtures/escaping.md" => escaping }
+/// # fn main() {}
+/// ```
+pub mod doctest_all {}
+
+/// All the locales must exist:
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!(base_dir = "../tests/fixtures", base = "guide", locales = ["", "es"]);
+/// ```
+pub mod missing_locale {}
+
+// The file is tested as usual and its content without the hidden lines is in `CLEAN_HIDDEN`.
+doc_comment::doctest!{ "../tests/fixtures/hidden.md", hidden_lines, expose_clean = CLEAN_HIDDEN }
+
+/// ```
+/// assert_eq!(
+///     test_ci::CLEAN_HIDDEN,
+///     "# Hidden lines\n\n```rust\nlet x = 1;\n#[allow(unused_variables)]\nlet y = x;\n\
+///      assert_eq!(x, 1);\n```\n\n```text\n# kept\n```\n",
+/// );
+/// ```
+pub mod clean_hidden {}
+
+// The comments are only removed from `CLEAN_COMMENTS`, the tested code blocks keep them.
+doc_comment::doctest!{ "../tests/fixtures/comments.md", comments, expose_clean = CLEAN_COMMENTS, strip_comments }
+
+/// ```
+/// assert_eq!(
+///     test_ci::CLEAN_COMMENTS,
+///     "# Comments\n\n```rust\nlet url = format!(\"http://{}\", \"example.com\");\n/// Documented.\n\
+///      fn check(url: &str) -> bool {\n    url.starts_with(\"http://\")\n}\nassert!(check(&url));\n```\n\n\
+///      ```text\n// kept\n```\n",
+/// );
+/// ```
+///
+/// ```compile_fail
+/// doc_comment::doctest!("../tests/fixtures/comments.md", strip_comments);
+/// ```
+pub mod clean_comments {}
+
+doc_comment::doctest!{ "../tests/fixtures/hidden.md", hidden_len, expose_len = HIDDEN_LEN }
+
+/// ```
+/// assert_eq!(test_ci::HIDDEN_LEN, include_str!("../tests/fixtures/hidden.md").len());
+/// ```
+///
+/// ```compile_fail
+/// doc_comment::doctest!("../tests/fixtures/hidden.md", expose_len = LEN, native);
+/// ```
+pub mod hidden_len_docs {}
+
+// The tested documentation isn't numbered, otherwise the code block wouldn't compile.
+doc_comment::doctest!{
+    "../tests/fixtures/hidden.md",
+    hidden_numbered,
+    expose_numbered = HIDDEN_NUMBERED,
+    expose_clean = HIDDEN_UNNUMBERED,
+}
+
+/// ```
+/// assert!(test_ci::HIDDEN_NUMBERED.contains(
+///     "```rust\n1 | let x = 1;\n2 | #[allow(unused_variables)]\n3 | let y = x;\n\
+///      4 | assert_eq!(x, 1);\n```\n",
+/// ));
+/// assert!(test_ci::HIDDEN_NUMBERED.contains("```text\n1 | # kept\n```"));
+/// assert!(!test_ci::HIDDEN_UNNUMBERED.contains("1 | "));
+/// ```
+///
+/// The numbered content is the clean one: the info strings are reduced to `rust` and
+/// `strip_comments` applies.
+///
+/// ```
+/// assert!(test_ci::MODES_NUMBERED.contains("```rust\n1 | panic!(\"expected\");\n```"));
+/// assert!(test_ci::COMMENTS_NUMBERED.starts_with(
+///     "# Comments\n\n```rust\n1 | let url = format!(\"http://{}\", \"example.com\");\n",
+/// ));
+/// ```
+pub mod numbered_docs {}
+doc_comment::doctest!{ "../tests/fixtures/modes.md", modes_numbered, expose_numbered = MODES_NUMBERED }
+doc_comment::doctest!{
+    "../tests/fixtures/comments.md",
+    comments_numbered,
+    expose_numbered = COMMENTS_NUMBERED,
+    strip_comments,
+}
+
+doc_comment::doctest!{
+    join = ["../tests/fixtures/join/intro.md", "../tests/fixtures/join/outro.md"],
+    into = joined_guide,
+    expose_clean = JOINED_GUIDE,
+}
+
+/// Both files are in the documentation of `joined_guide`:
+///
+/// ```
+/// assert_eq!(
+///     test_ci::JOINED_GUIDE,
+///     "# Guide\n\nThe intro.\n\n```rust\nlet intro = 1;\n```\n\n\n\
+///      The outro.\n\n```rust\nlet outro = 2;\n```\n",
+/// );
+/// ```
+///
+/// ```compile_fail
+/// doc_comment::doctest!(join = ["../tests/fixtures/join/intro.md"]);
+/// ```
+pub mod joined_files {}
+
+doc_comment::doctest!{
+    join = ["../tests/fixtures/join/unterminated.md", "../tests/fixtures/join/heading.md"],
+    into = joined_unterminated,
+    expose_clean = JOINED_UNTERMINATED,
+}
+doc_comment::doctest!{
+    join = ["../tests/fixtures/join/intro.md", "../tests/fixtures/join/outro.md"],
+    into = joined_with_rule,
+    separator = "\n---\n\n",
+    expose_clean = JOINED_WITH_RULE,
+}
+
+/// The default separator keeps the closing fence and the heading on their own lines:
+///
+/// ```
+/// assert_eq!(
+///     test_ci::JOINED_UNTERMINATED,
+///     "```rust\nlet unterminated = 1;\n```\n\n\n# Next part\n\n```rust\nlet next = 2;\n```\n",
+/// );
+/// assert!(test_ci::JOINED_WITH_RULE.contains("```\n\n---\n\nThe outro."));
+/// ```
+///
+/// ```compile_fail
+/// doc_comment::doctest!("../tests/fixtures/body.md", separator = "\n");
+/// ```
+pub mod join_separator {}
+
+doc_comment::doctest!{
+    join = ["../tests/fixtures/join/outro.md", "../tests/fixtures/join/intro.md"],
+    into = joined_as_listed,
+    order = "as-listed",
+    expose_clean = JOINED_AS_LISTED,
+}
+doc_comment::doctest!{
+    join = ["../tests/fixtures/join/outro.md", "../tests/fixtures/join/intro.md"],
+    into = joined_sorted,
+    order = "sorted",
+    expose_clean = JOINED_SORTED,
+}
+
+/// ```
+/// assert!(test_ci::JOINED_AS_LISTED.starts_with("The outro."));
+/// assert_eq!(test_ci::JOINED_SORTED, test_ci::JOINED_GUIDE);
+/// ```
+///
+/// ```compile_fail
+/// doc_comment::doctest!("../tests/fixtures/body.md", order = "sorted");
+/// ```
+///
+/// ```compile_fail
+/// doc_comment::doctest!(files = ["../tests/fixtures/body.md"], order = "reversed");
+/// ```
+pub mod join_order {}
+
+// Flags and options from newer versions are ignored with `allow_unknown`.
+doc_comment::doctest!{ "../tests/fixtures/body.md", body_unknown, allow_unknown, future_flag, future_option = 1 }
+
+/// Without `allow_unknown`, they're errors:
+///
+/// ```compile_fail
+/// doc_comment::doctest!("../tests/fixtures/body.md", body, future_flag);
+/// ```
+///
+/// ```compile_fail
+/// doc_comment::doctest!("../tests/fixtures/body.md", future_option = 1);
+/// ```
+pub mod unknown_flags {}
+
+// The first code block would fail but it's outside of the marked regions.
+doc_comment::doctest!{ "../tests/fixtures/curated.md", curated, markers, expose_clean = CURATED }
+
+/// ```
+/// assert_eq!(
+///     test_ci::CURATED,
+///     "```rust\nassert_eq!(1 + 1, 2);\n```\n\nCurated prose.\n\n```rust\nassert!(true);\n```\n",
+/// );
+/// ```
+///
+/// Unbalanced markers are errors:
+///
+/// ```compile_fail
+/// doc_comment::doctest!("../tests/fixtures/unbalanced.md", markers);
+/// ```
+pub mod curated_regions {}
+
+doc_comment::doctest!{ "../tests/fixtures/body.md", body_footer, footer, expose_clean = BODY_FOOTER }
+doc_comment::doctest!{ "../tests/fixtures/body.md", body_custom_footer, footer = "Tested!", expose_clean = BODY_CUSTOM_FOOTER }
+doc_comment::doctest!{
+    "../tests/fixtures/body.md",
+    body_escaped_footer,
+    footer = "say \"hi\"\tnow",
+    expose_clean = BODY_ESCAPED_FOOTER,
+}
+
+/// ```
+/// let prefix = "```\n\n_Examples verified by doc-comment v";
+/// let start = test_ci::BODY_FOOTER.rfind(prefix).unwrap();
+/// let version = &test_ci::BODY_FOOTER[start + prefix.len()..];
+/// assert!(version.ends_with("_\n"));
+/// assert_eq!(version.split('.').count(), 3);
+/// assert!(test_ci::BODY_CUSTOM_FOOTER.ends_with("```\n\nTested!\n"));
+/// assert!(test_ci::BODY_ESCAPED_FOOTER.ends_with("```\n\nsay \"hi\"\tnow\n"));
+/// ```
+pub mod footers {}
+
+doc_comment::doctest!{ "../tests/fixtures/body.md", body_source, source_comment, header, expose_clean = BODY_SOURCE }
+
+/// ```
+/// assert!(test_ci::BODY_SOURCE.starts_with(
+///     "<!-- doctest-source: ../tests/fixtures/body.md -->\n*From `../tests/fixtures/body.md`*\n\n## Body",
+/// ));
+/// ```
+pub mod source_comment {}
+
+doc_comment::doctest!{ "../tests/fixtures/body.md", body_copy, must_match = "../tests/fixtures/copy/body.md" }
+
+/// The copies must be identical:
+///
+/// ```compile_fail
+/// doc_comment::doctest!("../tests/fixtures/body.md", must_match = "../tests/fixtures/braces.md");
+/// ```
+pub mod must_match {}
+
+// `unignore` emits a warning saying how many code blocks it changed.
+#[allow(deprecated)]
+mod unignored {
+    doc_comment::doctest!{ "../tests/fixtures/ignored.md", ignored_examples, unignore }
+}
+
+/// ```compile_fail
+/// #![deny(deprecated)]
+/// doc_comment::doctest!("../tests/fixtures/ignored.md", unignore);
+/// # fn main() {}
+/// ```
+pub mod unignore {}
+
+doc_comment::doctest!{
+    "../tests/fixtures/placeholders.md",
+    placeholders,
+    replace = [("{{GREETING}}", "\"Hello \\\"{{CRATE}}\\\"!\""), ("{{CRATE}}", "test_ci")],
+    expose_clean = PLACEHOLDERS,
+}
+
+/// The replacements are made in order, so the second one also applies to the output of the first
+/// one:
+///
+/// ```
+/// assert_eq!(
+///     test_ci::PLACEHOLDERS,
+///     "# test_ci\n\n```rust\nlet greeting = \"Hello \\\"test_ci\\\"!\";\n\
+///      assert_eq!(greeting, \"Hello \\\"test_ci\\\"!\");\n```\n",
+/// );
+/// ```
+///
+/// ```compile_fail
+/// doc_comment::doctest!("../tests/fixtures/placeholders.md", replace = [("{{CRATE}}")]);
+/// ```
+pub mod replacements {}
+
+doc_comment::doctest!{ "../tests/fixtures/body.md", body_syntax, syntax_check }
+
+/// ```compile_fail
+/// doc_comment::doctest!("../tests/fixtures/malformed.md", syntax_check);
+/// ```
+pub mod syntax_check {}
+
+doc_comment::doctest!{ "../tests/fixtures/body.md", body_indent, lint_mixed_indent }
+
+/// ```compile_fail
+/// doc_comment::doctest!("../tests/fixtures/mixed_indent.md", lint_mixed_indent);
+/// ```
+pub mod lint_mixed_indent {}
+
+doc_comment::doctest!{ "../tests/fixtures/body.md", body_non_empty, non_empty }
+
+/// ```compile_fail
+/// doc_comment::doctest!("../tests/fixtures/empty.md", non_empty);
+/// ```
+///
+/// ```compile_fail
+/// doc_comment::doctest!("../tests/fixtures/blank.md", non_empty);
+/// ```
+///
+/// Without `non_empty`, an empty file is accepted:
+///
+/// ```
+/// doc_comment::doctest!("../tests/fixtures/empty.md");
+/// # fn main() {}
+/// ```
+pub mod non_empty {}
+
+doc_comment::doctest!{
+    "../tests/fixtures/body.md",
+    body_quiet,
+    quiet_track,
+    expose_clean = BODY_QUIET,
+}
+
+/// Only the tracking is hidden, the exposed items are still there:
+///
+/// ```
+/// assert!(test_ci::BODY_QUIET.starts_with("## Body"));
+/// ```
+pub mod quiet_track {}
+
+// The first line is blank and the next one indented, it mustn't become an indented code block
+// tested by rustdoc.
+doc_comment::doctest!{
+    "../tests/fixtures/indented_start.md",
+    indented_start,
+    expose_clean = INDENTED_START,
+}
+
+/// ```
+/// assert!(test_ci::INDENTED_START.starts_with("This intro was"));
+/// ```
+pub mod indented_first_line {}
+
+/// The examples of the file are only compiled.
+#[doc_comment::from_file("../tests/fixtures/network.md", no_run, edition2018, expose_clean = NETWORK)]
+pub mod network {}
+
+/// The modifiers are only in the tested documentation, not in the clean copy:
+///
+/// ```
+/// assert!(test_ci::NETWORK.contains("```rust\nuse std::io"));
+/// ```
+///
+/// ```compile_fail
+/// #[doc_comment::from_file("../tests/fixtures/network.md", no_rn)]
+/// pub mod network {}
+/// ```
+///
+/// ```compile_fail
+/// #[doc_comment::from_file("../tests/fixtures/network.md", split)]
+/// pub mod network {}
+/// ```
+pub mod from_file_options {}
+
+pub const JOIN_FIXTURES: &[&str] = doc_comment::list_files!("../tests/fixtures/join/*.md");
+
+/// The matched paths are sorted:
+///
+/// ```
+/// assert_eq!(
+///     test_ci::JOIN_FIXTURES,
+///     &[
+///         "../tests/fixtures/join/heading.md",
+///         "../tests/fixtures/join/intro.md",
+///         "../tests/fixtures/join/outro.md",
+///         "../tests/fixtures/join/unterminated.md",
+///     ],
+/// );
+/// ```
+///
+/// ```compile_fail
+/// const NONE: &[&str] = doc_comment::list_files!("../tests/fixtures/*.none");
+/// ```
+///
+/// ```compile_fail
+/// const DIRS: &[&str] = doc_comment::list_files!("../tests/*/body.md");
+/// ```
+pub mod list_files {}
+
+// Absolute paths (with spaces) are read and tracked like the relative ones.
+include!(concat!(env!("OUT_DIR"), "/absolute.rs"));
+
+// The quoted code block doesn't compile but it's not tested.
+doc_comment::doctest!{ "../tests/fixtures/quoted.md", quoted, skip_blockquotes }
+
+doc_comment::doctest!{
+    "../tests/fixtures/body.md",
+    body_header,
+    header,
+    header_strip = 1,
+    expose_clean = BODY_WITH_HEADER,
+}
+
+/// ```
+/// assert!(test_ci::BODY_WITH_HEADER.starts_with("*From `tests/fixtures/body.md`*\n\n## Body\n"));
+/// ```
+pub mod header {}
+
+doc_comment::doctest!{
+    "../tests/fixtures/no_newline.md",
+    no_newline,
+    append = "../tests/fixtures/footer.md",
+    expose_clean = NO_NEWLINE,
+}
+
+/// A new line is added at the end of the files which don't have one:
+///
+/// ```
+/// assert!(test_ci::NO_NEWLINE.contains("assert!(true);\n```\n\n"));
+/// assert!(test_ci::NO_NEWLINE.ends_with('\n'));
+/// ```
+pub mod no_newline_file {}
+
+doc_comment::doctest!{
+    "../tests/fixtures/mdbook/guide.md",
+    mdbook_guide,
+    mdbook_includes,
+    expose_clean = MDBOOK_GUIDE,
+}
+
+/// The `{{#include ...}}` directives are resolved relative to the file containing them:
+///
+/// ```
+/// assert!(test_ci::MDBOOK_GUIDE.contains("## Introduction\n\n```rust\nfn double("));
+/// assert!(test_ci::MDBOOK_GUIDE.contains("\n\nassert_eq!(double(2), 4);\n"));
+/// assert!(!test_ci::MDBOOK_GUIDE.contains("{{#include"));
+/// assert!(!test_ci::MDBOOK_GUIDE.contains("ANCHOR"));
+/// ```
+///
+/// Including a file recursively is an error:
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../tests/fixtures/mdbook/cycle_a.md", mdbook_includes);
+/// ```
+pub mod mdbook_includes {}
+
+doc_comment::doctest!{ "../tests/fixtures/anchors.rs", anchor_sum, anchor = "sum", expose_clean = ANCHOR_SUM }
+doc_comment::doctest!{ "../tests/fixtures/anchors.rs", anchor_max, anchor = "max" }
+
+/// Only the lines of the anchor are kept, in a rust code block:
+///
+/// ```
+/// assert!(test_ci::ANCHOR_SUM.starts_with("```rust\n    let v = vec![1, 2, 3];\n"));
+/// assert!(test_ci::ANCHOR_SUM.ends_with("sum::<i32>(), 6);\n```\n"));
+/// ```
+///
+/// The anchor must exist and be closed:
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../tests/fixtures/anchors.rs", anchor = "min");
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../tests/fixtures/anchors.rs", anchor = "unterminated");
+/// ```
+pub mod anchor {}
+
+// The code blocks are still tested.
+doc_comment::doctest!{ "../tests/fixtures/body.md", body_cfg_doctest, cfg_doctest, holder = "fn" }
+
+/// The generated item only exists when rustdoc looks for tests:
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../tests/fixtures/body.md", body, cfg_doctest, holder = "fn");
+///
+/// fn main() {
+///     body();
+/// }
+/// ```
+///
+/// ```edition2018
+/// doc_comment::doctest!("../tests/fixtures/body.md", body, holder = "fn");
+///
+/// fn main() {
+///     body();
+/// }
+/// ```
+pub mod cfg_doctest {}
+
+doc_comment::doctest!{
+    "../tests/fixtures/example.rs",
+    example_program,
+    fence_as = "rust",
+    hide_lines = "1..4,7",
+    expose_clean = EXAMPLE_PROGRAM,
+}
+
+/// ```
+/// assert_eq!(
+///     test_ci::EXAMPLE_PROGRAM,
+///     "```rust\n    let mut map = HashMap::new();\n    map.insert(\"a\", 1);\n    \
+///      assert_eq!(map[\"a\"], 1);\n```\n",
+/// );
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../tests/fixtures/example.rs", hide_lines = "1");
+/// ```
+pub mod hide_lines {}
+
+doc_comment::doctest!{
+    lit = "{} ",
+    "../tests/fixtures/braces.md",
+    braces,
+    details = "{summary}",
+    expose_clean = BRACES,
+}
+doc_comment::doctest!{ "../tests/fixtures/braces.md", braces_split, split, holder = "fn" }
+
+/// Braces in the content are kept as is:
+///
+/// ```
+/// assert_eq!(
+///     test_ci::BRACES,
+///     "<details><summary>{summary}</summary>\n\n{} # Braces {like} {{these}} and {}\n\n```rust\n\
+///      let s = format!(\"{}{{}}\", 1);\nassert_eq!(s, \"1{}\");\n```\n\n\n</details>\n",
+/// );
+/// ```
+pub mod braces_content {}
+
+doc_comment::doctest!{ "../tests/fixtures/CHANGELOG.md", changelog, optional }
+doc_comment::doctest!{ "../tests/fixtures/body.md", body_optional, optional, expose_clean = BODY_OPTIONAL }
+/// The changelog, if any.
+///
+#[doc = doc_comment::doctest!("../tests/fixtures/CHANGELOG.md", attach, optional)]
+pub struct NoChangelog;
+
+/// Only the missing files are skipped:
+///
+/// ```
+/// assert!(test_ci::BODY_OPTIONAL.starts_with("## Body\n"));
+/// ```
+///
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../tests/fixtures/CHANGELOG.md", changelog);
+/// ```
+pub mod optional {}
+
+// Only one of them is tested.
+doc_comment::doctest!{ "../tests/fixtures/escaping.md", escaping_once, once }
+doc_comment::doctest!{ "../tests/fixtures/escaping.md", escaping_twice, once }
+
+/// ```compile_fail,edition2018
+/// doc_comment::doctest!("../tests/fixtures/body.md", first, once, holder = "fn");
+/// doc_comment::doctest!("../tests/fixtures/body.md", second, once, holder = "fn");
+///
+/// fn main() {
+///     first();
+///     second();
+/// }
+/// ```
+///
+/// ```edition2018
+/// doc_comment::doctest!("../tests/fixtures/body.md", first, once, holder = "fn");
+/// doc_comment::doctest!("../tests/fixtures/body.md", second, holder = "fn");
+///
+/// fn main() {
+///     first();
+///     second();
+/// }
+/// ```
+pub mod once {}
+
+doc_comment::doctest!{ "../tests/fixtures/no_std.md", no_std_examples, no_std }
+
+// The code blocks of `#[doc(hidden)]` items are still tested.
+doc_comment::doctest!{ "../tests/fixtures/body.md", hidden_body, hidden, holder = "fn" }
+
+// The `use` paths of the 2015 edition need an `extern crate`.
+doc_comment::doctest!{ "../tests/fixtures/legacy.md", legacy_examples, extern_crate = "doc_comment" }
+
+// The second code block uses the `Point` type and the `origin` variable from the first one.
+doc_comment::doctest!{ "../tests/fixtures/tutorial.md", build_up = tutorial }
+// The `pub struct` of the first code block is used in the second one.
+doc_comment::doctest!{ "../tests/fixtures/users.md", build_up = users }
+
+/// The code blocks items cannot be re-exported since they're not part of the crate:
+///
+/// ```compile_fail
+/// doc_comment::doctest!("../tests/fixtures/users.md", users, reexport = ["User"]);
+/// ```
+pub mod reexport {}
+
+doc_comment::doctest!{
+    "../tests/fixtures/body.md",
+    body_details,
+    details = "Full <body>",
+    expose_clean = BODY_DETAILS,
+}
+doc_comment::doctest!{
+    "../tests/fixtures/body.md",
+    body_quoted_details,
+    details = "A \"b\"",
+    expose_clean = BODY_QUOTED_DETAILS,
+}
+
+/// ```
+/// assert!(test_ci::BODY_DETAILS
+///     .starts_with("<details><summary>Full &lt;body&gt;</summary>\n\n## Body\n"));
+/// assert!(test_ci::BODY_DETAILS.contains("```rust\n"));
+/// assert!(test_ci::BODY_DETAILS.ends_with("\n\n</details>\n"));
+/// assert!(test_ci::BODY_QUOTED_DETAILS.starts_with("<details><summary>A \"b\"</summary>\n"));
+/// ```
+pub mod details {}
+
+// The code blocks which already have an edition keep it.
+doc_comment::doctest!{ "../tests/fixtures/edition2015.md", edition2015, edition2015 }
+pub const EDITION2015: &str =
+    doc_comment::doctest!("../tests/fixtures/edition2015.md", attach, edition2015);
+doc_comment::doctest!{ "../tests/fixtures/edition2018.md", edition2018, edition2018 }
+doc_comment::doctest!{ "../tests/fixtures/edition2021.md", edition2021, edition2021 }
+
+/// The crate uses the 2015 edition already, so the flag is only visible in the content:
+///
+/// ```
+/// assert!(test_ci::EDITION2015.contains("```rust,edition2015\n// `async`"));
+/// assert!(test_ci::EDITION2015.contains("```rust,edition2018\n"));
+/// ```
+pub mod edition_flags {}
+
+/// The `source_markers` comments are hidden:
+///
+/// ```
+/// assert!(!test_ci::CLEAN_BODY.contains("source:"));
+/// ```
+pub mod hidden_source_markers {}
+
+// The documentation of `doc_comment` goes before the other attributes (doc comments included), so
+// this doctest is complete.
+#[doc = "assert_eq!(x, 1);\n```"]
+#[doc_comment::doc_comment("```\nlet x = 1;")]
+#[derive(Debug)]
+pub struct DocFirst;
+
+// With `after`, it goes after the given attribute.
+#[doc_comment::doc_comment("let x = 1;", after = "doc")]
+#[doc = "```"]
+#[doc = "assert_eq!(x, 1);\n```"]
+pub struct DocAfter;
+
+#[doc_comment::doc_comment("After `derive`.", after = "derive")]
+#[derive(Debug, Clone, Copy)]
+pub struct DocAfterDerive;
+
+// A `'"'` char literal doesn't end the generated string.
+#[doc_comment::doc_comment("```\nassert_eq!('", '"', "'.len_utf8(), 1);\n```")]
+pub struct DocQuoteChar;
+
+// Byte chars and raw strings are documented with their value.
+#[doc_comment::doc_comment("```\nassert_eq!(b'", b'"', "', 34);\nassert_eq!(r\"", r"\d", "\".len(), 2);\n```")]
+pub struct DocByteCharRawString;
+
+// With `position = "before"`, the file goes before the `///` comments, even the ones written
+// before the attribute.
+/// assert_eq!(x, 1);
+#[doc_comment::doc_comment(include_str!("../tests/fixtures/position/start.md"), position = "before")]
+/// ```
+pub struct DocPositionBefore;
+
+// With `position = "after"`, it goes after all the `///` comments.
+/// ```
+/// let x = 1;
+#[doc_comment::doc_comment(include_str!("../tests/fixtures/position/end.md"), position = "after")]
+#[derive(Debug)]
+/// let y = 2;
+pub struct DocPositionAfter;
+
+/// `position` and `after` cannot be used together:
+///
+/// ```compile_fail,edition2018
+/// #[doc_comment::doc_comment("Doc.", after = "derive", position = "after")]
+/// #[derive(Debug)]
+/// pub struct Both;
+/// ```
+///
+/// ```compile_fail,edition2018
+/// #[doc_comment::doc_comment("Doc.", position = "middle")]
+/// pub struct Middle;
+/// ```
+pub mod doc_position {}
+
+/// The named attribute must exist:
+///
+/// ```compile_fail,edition2018
+/// #[doc_comment::doc_comment("After `derive`.", after = "derive")]
+/// pub struct DocAfterDerive;
+/// # fn main() {}
+/// ```
+pub mod doc_after {}
+
+/// `#[doc_comment()]` doesn't generate any `#[doc]` attribute, so `missing_docs` still fires:
+///
+/// ```compile_fail,edition2018
+/// #![deny(missing_docs)]
+/// //! Crate docs.
+///
+/// #[doc_comment::doc_comment()]
+/// pub fn undocumented() {}
+///
+/// fn main() {}
+/// ```
+pub mod empty_doc_comment {}
+
+/// Items generated by `doc_comment` keep their initializers:
+///
+/// ```
+/// assert_eq!(test_ci::items::SUM, 3);
+/// assert_eq!(test_ci::items::CAST, 258);
+/// assert_eq!(test_ci::items::NEGATIVE, -1);
+/// assert_eq!(test_ci::items::KEYWORD, 2);
+/// assert_eq!(test_ci::items::counter(), 21);
+/// test_ci::items::gated();
+/// assert!(!test_ci::items::LESS);
+/// assert_eq!(test_ci::items::S, "a b");
+/// assert_eq!(test_ci::items::len(), 3);
+/// assert_eq!(test_ci::items::double_ref(&&4), 4);
+/// assert!(test_ci::items::closure());
+/// assert_eq!(test_ci::items::double(2), 4);
+/// assert_eq!(test_ci::items::first(&[1, 2]), Some(&1));
+/// assert_eq!(test_ci::items::parse(), vec![4, 0]);
+/// assert_eq!(test_ci::items::Wrapper("a").show(), vec![vec!["a".to_owned()]]);
+/// ```
+pub mod items {
+    #[doc_comment::doc_comment("a const")]
+    pub const SUM: u8 = 1+2;
+    #[doc_comment::doc_comment("a static")]
+    pub static CAST: u16 = 2 as u16 + 256;
+    #[doc_comment::doc_comment("a negative value")]
+    pub const NEGATIVE: i8 = 1 - 2 * 1;
+    #[doc_comment::doc_comment("a value behind a keyword")]
+    pub const KEYWORD: u8 = if true { 2 } else { 1 };
+
+    #[doc_comment::doc_comment("a comparison with a negative literal")]
+    pub const LESS: bool = 0 < -1;
+    #[doc_comment::doc_comment("a string")]
+    pub const S: &str = "a b";
+    #[doc_comment::doc_comment("a path and a method call")]
+    pub fn len() -> usize {
+        ::std::string::String::from(S).len()
+    }
+    #[doc_comment::doc_comment("a double reference")]
+    pub fn double_ref(x: & &u8) -> u8 {
+        **x
+    }
+    #[doc_comment::doc_comment("a closure")]
+    pub fn closure() -> bool {
+        let f = | | true;
+        f() && !LESS
+    }
+
+    #[doc_comment::doc_comment("a function with bounds")]
+    pub fn double<T: ::std::ops::Add<Output = T> + Copy>(x: T) -> T {
+        x + x
+    }
+    #[doc_comment::doc_comment("a function with lifetimes and a where clause")]
+    pub fn first<'a, T: 'a + Clone>(x: &'a [T]) -> Option<&'a T>
+    where
+        T: ::std::fmt::Debug,
+    {
+        x.iter().next()
+    }
+    #[doc_comment::doc_comment("a turbofish")]
+    pub fn parse() -> Vec<u8> {
+        vec!["4".parse::<u8>().unwrap(), Vec::<u8>::new().len() as u8]
+    }
+    #[doc_comment::doc_comment("a generic struct")]
+    pub struct Wrapper<'a, T: ?Sized + 'a>(pub &'a T)
+    where
+        T: ::std::fmt::Display;
+    #[doc_comment::doc_comment("a generic impl")]
+    impl<'a, T> Wrapper<'a, T>
+    where
+        T: ::std::fmt::Display + ?Sized,
+    {
+        #[doc_comment::doc_comment("a method with nested generics")]
+        pub fn show(&self) -> Vec<Vec<String>> {
+            vec![vec![format!("{}", self.0)]]
+        }
+    }
+
+    #[doc_comment::doc_comment("a mutable static")]
+    static mut COUNTER: u32 = 10 * 2 + 1;
+
+    #[doc_comment::doc_comment("feature gated", doc_cfg(feature = "gated"))]
+    pub fn gated() {}
+
+    #[doc_comment::doc_comment("reads the mutable static")]
+    pub fn counter() -> u32 {
+        unsafe { COUNTER }
+    }
+}
+
+/// A file's content can document any item, the example it contains being tested for each one:
+///
+/// ```
+/// use test_ci::file_docs::{area, Rect, Shape};
+///
+/// assert_eq!(area(&Rect { w: 1, h: 4 }), 4);
+/// assert_eq!(Shape::Square(3).side(), Some(3));
+/// assert_eq!(Shape::Dot.side(), None);
+/// assert_eq!(test_ci::file_docs::nested::ANSWER, 42);
+/// ```
+#[deny(missing_docs)]
+pub mod file_docs {
+    #[doc_comment::doc_comment(include_str!("../tests/fixtures/item_docs.md"))]
+    pub fn area(r: &Rect) -> u32 {
+        r.w * r.h
+    }
+
+    #[doc_comment::doc_comment(include_str!("../tests/fixtures/item_docs.md"))]
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct Rect {
+        /// The width.
+        pub w: u32,
+        /// The height.
+        pub h: u32,
+    }
+
+    #[doc_comment::doc_comment(include_str!("../tests/fixtures/item_docs.md"))]
+    pub enum Shape {
+        /// A square with the given side.
+        Square(u32),
+        /// Nothing to measure.
+        Dot,
+    }
+
+    impl Shape {
+        /// The side of the square.
+        pub fn side(&self) -> Option<u32> {
+            match *self {
+                Shape::Square(side) => Some(side),
+                Shape::Dot => None,
+            }
+        }
+    }
+
+    #[doc_comment::doc_comment(include_str!("../tests/fixtures/item_docs.md"))]
+    pub mod nested {
+        /// The answer.
+        pub const ANSWER: u8 = 42;
+    }
+}
+
+doc_comment::doctest!{
+    "../tests/fixtures/platforms.md",
+    variants = [
+        (unix, platforms_unix),
+        (windows, platforms_windows),
+        (not(any(unix, windows)), platforms_other),
+    ],
+}
+
+#[cfg(unix)]
+#[allow(unused_imports)]
+use platforms_unix as _;
+#[cfg(windows)]
+#[allow(unused_imports)]
+use platforms_windows as _;
+
+/// ```compile_fail
+/// doc_comment::doctest!("../tests/fixtures/body.md", variants = [(unix)]);
+/// ```
+///
+/// ```compile_fail
+/// doc_comment::doctest!("../tests/fixtures/body.md", attach, variants = [(unix, body_unix)]);
+/// ```
+pub mod cfg_variants {}
+
+doc_comment::doctest!{ "../tests/fixtures/links/guide.md", linked_guide, check_relative_links }
+
+/// ```compile_fail
+/// doc_comment::doctest!("../tests/fixtures/links/dangling.md", check_relative_links);
+/// ```
+///
+/// The broken links are only reported with `check_relative_links = "warn"`:
+///
+/// ```compile_fail
+/// #![deny(deprecated)]
+/// doc_comment::doctest!("../tests/fixtures/links/dangling.md", check_relative_links = "warn");
+/// # fn main() {}
+/// ```
+///
+/// ```
+/// #![allow(deprecated)]
+/// doc_comment::doctest!("../tests/fixtures/links/dangling.md", check_relative_links = "warn");
+/// # fn main() {}
+/// ```
+pub mod relative_links {}
+
+// Same as the "Generic documentation" example of the crate, but the documentation is indented
+// like the rest of the macro. Without `dedent`, the indented lines would be a code block tested
+// as rust code (and failing).
+macro_rules! gen_dedented {
+    ($($tyname:ident),*) => {
+        $(
+            #[doc_comment::doc_comment(
+                "This is a wonderful generated struct!
+
+                You can use it as follow:
+
+                ```
+                let x = test_ci::", $tyname, " {
+                    field1: 0,
+                    field2: 0,
+                };
+                println!(\"Created a new instance of ", $tyname, ": {:?}\", x);
+                ```",
+                dedent,
+            )]
+            #[derive(Debug)]
+            pub struct $tyname {
+                pub field1: u8,
+                pub field2: u16,
+            }
+        )*
+    };
+}
+
+gen_dedented!(DedentedOne, DedentedTwo);
+
+// The module is named after the file, so its test can be run with `cargo test --doc quick_start`.
+doc_comment::doctest!{ "../tests/fixtures/Quick-Start.md", auto_name }
+
+#[allow(unused_imports)]
+use quick_start as _;
+
+/// ```compile_fail
+/// doc_comment::doctest!("../tests/fixtures/Quick-Start.md", start, auto_name);
+/// ```
+pub mod auto_name {}
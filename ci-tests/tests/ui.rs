@@ -0,0 +1,8 @@
+// The `compile_fail` doctests of `lib.rs` pass on any error, panics included: these tests check
+// the error messages and their spans.
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+    t.pass("tests/ui/pass/*.rs");
+}
@@ -0,0 +1,3 @@
+doc_comment::doctest!("/", root);
+
+fn main() {}
@@ -0,0 +1,3 @@
+doc_comment::doctest!(1, readme);
+
+fn main() {}
@@ -0,0 +1,3 @@
+doc_comment::doctest!("../../../README.md", match);
+
+fn main() {}
@@ -0,0 +1,3 @@
+doc_comment::doctest!("../../../tests/fixtures/mixed_indent.md", lint_mixed_indent);
+
+fn main() {}
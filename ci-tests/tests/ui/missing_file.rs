@@ -0,0 +1,3 @@
+doc_comment::doctest!("../../../tests/fixtures/missing.md", missing);
+
+fn main() {}
@@ -0,0 +1,14 @@
+doc_comment::doctest!(
+    "../../../tests/fixtures/body.md",
+    body,
+    must_match = "../../../tests/fixtures/copy/body_changed.md",
+);
+
+// The files are compared as written, so the indentation of the first line counts.
+doc_comment::doctest!(
+    "../../../tests/fixtures/body.md",
+    body_indented,
+    must_match = "../../../tests/fixtures/copy/body_indented.md",
+);
+
+fn main() {}
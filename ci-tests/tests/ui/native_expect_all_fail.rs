@@ -0,0 +1,3 @@
+doc_comment::doctest!("../../../tests/fixtures/antipatterns.md", native, expect_all_fail);
+
+fn main() {}
@@ -0,0 +1,5 @@
+doc_comment::doctest!("../../../tests/fixtures/prompt.md", native, lint_prompts);
+
+doc_comment::doctest!("../../../tests/fixtures/template.md", native, lint_templates = "{{");
+
+fn main() {}
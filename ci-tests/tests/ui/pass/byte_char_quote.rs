@@ -0,0 +1,6 @@
+extern crate doc_comment;
+
+#[doc_comment::doc_comment("A `", b'"', "` byte.")]
+pub struct Quote;
+
+fn main() {}
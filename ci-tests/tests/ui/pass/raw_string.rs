@@ -0,0 +1,6 @@
+extern crate doc_comment;
+
+#[doc_comment::doc_comment(r"Matches `\d+`.", r#"Quoted: "\w""#)]
+pub struct Pattern;
+
+fn main() {}
@@ -0,0 +1,3 @@
+doc_comment::doctest!("../../../README.md", readme, tab_widht = 4);
+
+fn main() {}
@@ -0,0 +1,4 @@
+#[doc_comment::doc_comment(format!("{}", 1))]
+pub struct Formatted;
+
+fn main() {}
@@ -0,0 +1,4 @@
+#[doc_comment::doc_comment("Docs ", [1, 2])]
+pub struct Bracketed;
+
+fn main() {}
@@ -0,0 +1,504 @@
+//! Scanning of the markdown fenced code blocks.
+//!
+//! Every feature of `doc-comment`'s `doctest` which needs to look at (or rewrite) code blocks goes
+//! through [`scan_fences`] so they all agree on what is a code block. It's a separate crate since
+//! a proc-macro crate can only export macros, so other macro authors can use it as well.
+
+use std::ops::Range;
+
+/// Iterates over the lines of `s`, keeping their line ending.
+pub fn lines_with_endings(s: &str) -> impl Iterator<Item = &str> {
+    let mut rest = s;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let end = rest
+            .find('\n')
+            .map(|pos| pos + 1)
+            .unwrap_or_else(|| rest.len());
+        let (line, next) = rest.split_at(end);
+        rest = next;
+        Some(line)
+    })
+}
+
+/// The marker of a fence line.
+struct Marker<'a> {
+    c: char,
+    len: usize,
+    indent: usize,
+    info: &'a str,
+}
+
+/// If `line` opens (or closes) a fenced code block, returns its marker.
+fn parse_marker<'a>(line: &'a str) -> Option<Marker<'a>> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let marker = &line[indent..];
+    let c = marker.chars().next()?;
+    if c != '`' && c != '~' {
+        return None;
+    }
+    let len = marker.len() - marker.trim_start_matches(c).len();
+    if len < 3 {
+        return None;
+    }
+    let info = marker[len..].trim();
+    if c == '`' && info.contains('`') {
+        return None;
+    }
+    Some(Marker {
+        c,
+        len,
+        indent,
+        info,
+    })
+}
+
+/// A fenced code block found by [`scan_fences`].
+pub struct FenceSpan<'a> {
+    /// Byte range of the whole code block, from the start of the opening marker line to the end
+    /// of the closing marker line.
+    pub range: Range<usize>,
+    /// Byte range of the body, between the marker lines.
+    pub body_range: Range<usize>,
+    /// Position right after the opening marker (and before the info string).
+    pub marker_end: usize,
+    /// Number of spaces before the opening marker.
+    pub indent: usize,
+    /// The info string (what comes after the opening marker), trimmed.
+    pub info: &'a str,
+    /// The body (`&content[body_range]`).
+    pub body: &'a str,
+    /// Line (starting at 1) of the opening marker.
+    pub line: usize,
+    /// Line of the closing marker. If the code block isn't closed, it's the last line.
+    pub end_line: usize,
+    /// `false` if the code block runs until the end of the content.
+    pub closed: bool,
+}
+
+impl<'a> FenceSpan<'a> {
+    /// Returns what rustdoc does with this code block.
+    pub fn run_status(&self) -> RunStatus {
+        if !is_rust_fence(self.info) {
+            return RunStatus::NotRust;
+        }
+        let mut status = RunStatus::Run;
+        for token in self.info.split(|c: char| c == ',' || c.is_whitespace()) {
+            match token {
+                "ignore" => return RunStatus::Ignored,
+                "no_run" | "compile_fail" => status = RunStatus::Compile,
+                _ => {}
+            }
+        }
+        status
+    }
+}
+
+/// What rustdoc does with a code block.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RunStatus {
+    /// The code block is compiled and run.
+    Run,
+    /// The code block is only compiled (`no_run` and `compile_fail`).
+    Compile,
+    /// The code block is `ignore`d.
+    Ignored,
+    /// The code block isn't rust code so it's not tested at all.
+    NotRust,
+}
+
+/// Returns the fenced code blocks of `content`, following the CommonMark rules: a code block
+/// starts with at least three backticks or tildes (indented by at most three spaces) and ends
+/// with a line containing only at least as many of the same character. If it's never closed, it
+/// runs until the end of the content.
+pub fn scan_fences<'a>(content: &'a str) -> Vec<FenceSpan<'a>> {
+    let mut fences = Vec::new();
+    let mut lines = lines_with_endings(content);
+    let mut pos = 0;
+    let mut line_nb = 0;
+
+    while let Some(line) = lines.next() {
+        let start = pos;
+        pos += line.len();
+        line_nb += 1;
+        let marker = match parse_marker(line) {
+            Some(m) => m,
+            None => continue,
+        };
+        let fence_line = line_nb;
+        let body_start = pos;
+        let mut body_end = content.len();
+        let mut closed = false;
+        for line in &mut lines {
+            line_nb += 1;
+            pos += line.len();
+            match parse_marker(line) {
+                Some(ref end)
+                    if end.c == marker.c && end.len >= marker.len && end.info.is_empty() =>
+                {
+                    body_end = pos - line.len();
+                    closed = true;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        fences.push(FenceSpan {
+            range: start..pos,
+            body_range: body_start..body_end,
+            marker_end: start + marker.indent + marker.len,
+            indent: marker.indent,
+            info: marker.info,
+            body: &content[body_start..body_end],
+            line: fence_line,
+            end_line: line_nb,
+            closed,
+        });
+    }
+    fences
+}
+
+/// A fenced code block inside a blockquote (with its lines starting with `> `). [`scan_fences`]
+/// doesn't return them but rustdoc tests them too.
+pub struct QuotedFence<'a> {
+    /// Byte range of what comes after the opening marker on its line (the info string, not
+    /// trimmed, without the line ending).
+    pub info_range: Range<usize>,
+    /// The info string, trimmed.
+    pub info: &'a str,
+}
+
+/// Returns the length of the blockquote markers (`>`, optionally followed by a space, possibly
+/// nested) at the beginning of `line`, or 0 if it's not in a blockquote.
+fn quote_prefix_len(line: &str) -> usize {
+    let mut len = 0;
+    loop {
+        let rest = &line[len..];
+        let indent = rest.len() - rest.trim_start_matches(' ').len();
+        if indent > 3 || !rest[indent..].starts_with('>') {
+            return len;
+        }
+        len += indent + 1;
+        if line[len..].starts_with(' ') {
+            len += 1;
+        }
+    }
+}
+
+/// Returns the fenced code blocks of `content` which are inside blockquotes. A code block ends
+/// with its closing marker or with the blockquote. Blockquotes inside (regular) code blocks are
+/// ignored.
+pub fn scan_quoted_fences<'a>(content: &'a str) -> Vec<QuotedFence<'a>> {
+    let fences = scan_fences(content);
+    let mut quoted = Vec::new();
+    // The marker of the quoted code block being read, if any.
+    let mut open: Option<(char, usize)> = None;
+    let mut pos = 0;
+    for line in lines_with_endings(content) {
+        let start = pos;
+        pos += line.len();
+        let prefix = quote_prefix_len(line);
+        if prefix == 0 || fences.iter().any(|f| f.range.contains(&start)) {
+            open = None;
+            continue;
+        }
+        let marker = parse_marker(&line[prefix..]);
+        match (open, marker) {
+            (Some((c, len)), Some(ref end))
+                if end.c == c && end.len >= len && end.info.is_empty() =>
+            {
+                open = None;
+            }
+            (Some(_), _) => {}
+            (None, Some(marker)) => {
+                open = Some((marker.c, marker.len));
+                let info_start = start + prefix + marker.indent + marker.len;
+                let info_end = start + line.trim_end_matches(|c| c == '\n' || c == '\r').len();
+                quoted.push(QuotedFence {
+                    info_range: info_start..info_end,
+                    info: marker.info,
+                });
+            }
+            (None, None) => {}
+        }
+    }
+    quoted
+}
+
+/// Calls `f` on the info string and the body of each fenced code block of `content` and returns
+/// the updated content. Everything outside of code blocks is kept as is.
+pub fn rewrite_fences<F: FnMut(&mut String, &mut String)>(content: &str, mut f: F) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut pos = 0;
+
+    for fence in scan_fences(content) {
+        out.push_str(&content[pos..fence.range.start]);
+        let mut info = fence.info.to_owned();
+        let mut body = fence.body.to_owned();
+        f(&mut info, &mut body);
+        let opening = &content[fence.range.start..fence.body_range.start];
+        if info == fence.info {
+            out.push_str(opening);
+        } else {
+            out.push_str(&content[fence.range.start..fence.marker_end]);
+            out.push_str(&info);
+            out.push_str(&opening[opening.trim_end().len()..]);
+        }
+        out.push_str(&body);
+        out.push_str(&content[fence.body_range.end..fence.range.end]);
+        pos = fence.range.end;
+    }
+    out.push_str(&content[pos..]);
+    out
+}
+
+/// Prefixes each line of the body of a code block with its number, like `1 | `. The numbers are
+/// right-aligned.
+pub fn number_lines(body: &str) -> String {
+    let width = lines_with_endings(body).count().to_string().len();
+    let mut out = String::with_capacity(body.len());
+    for (nb, line) in lines_with_endings(body).enumerate() {
+        out.push_str(&format!("{:>width$} | {}", nb + 1, line, width = width));
+    }
+    out
+}
+
+/// Returns `true` if the info string of a code block contains `modifier`.
+pub fn has_fence_modifier(info: &str, modifier: &str) -> bool {
+    info.split(|c: char| c == ',' || c.is_whitespace())
+        .any(|m| m == modifier)
+}
+
+/// Adds `modifier` to the info string of a code block if it doesn't already have it.
+pub fn add_fence_modifier(info: &mut String, modifier: &str) {
+    if info.is_empty() {
+        info.push_str(modifier);
+    } else if !has_fence_modifier(info, modifier) {
+        info.push(',');
+        info.push_str(modifier);
+    }
+}
+
+/// Removes `modifier` from the info string of a code block. If nothing is left, the info string
+/// becomes `rust` so the code block is still rust code.
+pub fn remove_fence_modifier(info: &mut String, modifier: &str) {
+    if !has_fence_modifier(info, modifier) {
+        return;
+    }
+    let kept = info
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|m| !m.is_empty() && *m != modifier)
+        .collect::<Vec<_>>();
+    *info = if kept.is_empty() {
+        "rust".to_owned()
+    } else {
+        kept.join(",")
+    };
+}
+
+/// Returns `true` if rustdoc considers a code block with this info string as Rust code.
+pub fn is_rust_fence(info: &str) -> bool {
+    let mut seen_rust = false;
+    let mut seen_other = false;
+    for token in info
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|t| !t.is_empty())
+    {
+        match token {
+            "rust" => seen_rust = true,
+            "should_panic" | "no_run" | "ignore" | "compile_fail" | "test_harness"
+            | "allow_fail" | "standalone_crate" => {}
+            t if t.starts_with("ignore-") || t.starts_with("edition") => {}
+            t if t.len() == 5
+                && t.starts_with('E')
+                && t[1..].bytes().all(|b| b.is_ascii_digit()) => {}
+            _ => seen_other = true,
+        }
+    }
+    seen_rust || !seen_other
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn infos_and_bodies(content: &str) -> Vec<(&str, &str)> {
+        scan_fences(content)
+            .iter()
+            .map(|f| (f.info, f.body))
+            .collect()
+    }
+
+    #[test]
+    fn backticks_and_tildes() {
+        let content = "a\n```rust\nlet x = 1;\n```\nb\n~~~text\nhello\n~~~\n";
+        assert_eq!(
+            infos_and_bodies(content),
+            vec![("rust", "let x = 1;\n"), ("text", "hello\n")],
+        );
+        let fences = scan_fences(content);
+        assert_eq!(&content[fences[0].range.start..fences[0].marker_end], "```");
+        assert_eq!(&content[fences[1].range.start..fences[1].marker_end], "~~~");
+        assert_eq!(
+            &content[fences[0].range.clone()],
+            "```rust\nlet x = 1;\n```\n"
+        );
+        assert_eq!((fences[0].line, fences[0].end_line), (2, 4));
+        assert_eq!((fences[1].line, fences[1].end_line), (6, 8));
+    }
+
+    #[test]
+    fn indented_fences() {
+        let content = "   ```\n   code\n   ```\n    ```\n    not a fence\n    ```\n";
+        let fences = scan_fences(content);
+        assert_eq!(fences.len(), 1);
+        assert_eq!(fences[0].indent, 3);
+        assert_eq!(fences[0].body, "   code\n");
+    }
+
+    #[test]
+    fn nested_fences() {
+        // A longer fence can contain shorter ones.
+        let content = "````markdown\n```rust\nlet x = 1;\n```\n````\n";
+        assert_eq!(
+            infos_and_bodies(content),
+            vec![("markdown", "```rust\nlet x = 1;\n```\n")],
+        );
+        // Backticks don't close a tilde fence.
+        let content = "~~~\n```\n~~~\n";
+        assert_eq!(infos_and_bodies(content), vec![("", "```\n")]);
+        // A closing marker cannot have an info string.
+        let content = "```\n```rust\n```\n";
+        assert_eq!(infos_and_bodies(content), vec![("", "```rust\n")]);
+    }
+
+    #[test]
+    fn escaped_and_inline() {
+        // Backticks in the info string of a backtick fence means it's inline code.
+        let content = "``` a ` b ```\n\\```\nnot code\n";
+        assert!(scan_fences(content).is_empty());
+        assert!(scan_fences("``not a fence``\n").is_empty());
+    }
+
+    #[test]
+    fn tables() {
+        // Code spans in tables are never code blocks, even with three backticks.
+        let content = "| Option | Example |\n|---|---|\n| `x` | `let x = 1;` |\n\
+                       | ```y``` | ```let y = 2;``` |\n```rust\nlet s = \"| a | b |\";\n```\n\
+                       | `z` | ```\n```text\n|---|\n```\n";
+        assert_eq!(
+            infos_and_bodies(content),
+            vec![("rust", "let s = \"| a | b |\";\n"), ("text", "|---|\n")],
+        );
+        let fences = scan_fences(content);
+        assert_eq!((fences[0].line, fences[0].end_line), (5, 7));
+        assert_eq!((fences[1].line, fences[1].end_line), (9, 11));
+    }
+
+    #[test]
+    fn quoted_fences() {
+        let content = "> Quote:\n> ```rust\n> let x = 1;\n> ```\n\n>> ~~~\n>> nested\n\n\
+                       ```text\n> ```rust\n```\n>```sh,no_run \n> ```\n";
+        let quoted = scan_quoted_fences(content);
+        assert_eq!(
+            quoted.iter().map(|f| f.info).collect::<Vec<_>>(),
+            vec!["rust", "", "sh,no_run"],
+        );
+        assert_eq!(&content[quoted[0].info_range.clone()], "rust");
+        assert_eq!(&content[quoted[1].info_range.clone()], "");
+        assert_eq!(&content[quoted[2].info_range.clone()], "sh,no_run ");
+        assert_eq!(quote_prefix_len("   > > x"), 7);
+        assert_eq!(quote_prefix_len("    > x"), 0);
+    }
+
+    #[test]
+    fn unclosed_fence() {
+        let content = "text\n```\nlet x = 1;";
+        let fences = scan_fences(content);
+        assert_eq!(fences.len(), 1);
+        assert!(!fences[0].closed);
+        assert_eq!(fences[0].body, "let x = 1;");
+        assert_eq!(fences[0].end_line, 3);
+    }
+
+    #[test]
+    fn numbered_lines() {
+        assert_eq!(
+            number_lines("let a = 1;\nlet b = a;\n"),
+            "1 | let a = 1;\n2 | let b = a;\n"
+        );
+        let body = "x\n".repeat(10);
+        let numbered = number_lines(&body);
+        assert!(numbered.starts_with(" 1 | x\n 2 | x\n"));
+        assert!(numbered.ends_with("\n10 | x\n"));
+        assert_eq!(number_lines(""), "");
+    }
+
+    #[test]
+    fn rewrite_keeps_the_rest() {
+        let content = "a\n``` rust \nx\n```\r\nb";
+        assert_eq!(rewrite_fences(content, |_, _| {}), content);
+        assert_eq!(
+            rewrite_fences(content, |info, body| {
+                *info = "text".to_owned();
+                body.push_str("y\n");
+            }),
+            "a\n```text \nx\ny\n```\r\nb",
+        );
+    }
+
+    #[test]
+    fn rust_fences() {
+        assert!(is_rust_fence(""));
+        assert!(is_rust_fence("rust"));
+        assert!(is_rust_fence("no_run,should_panic"));
+        assert!(is_rust_fence("edition2018 ignore"));
+        assert!(is_rust_fence("compile_fail,E0308"));
+        assert!(is_rust_fence("rust,custom"));
+        assert!(!is_rust_fence("text"));
+        assert!(!is_rust_fence("sh,no_run"));
+    }
+
+    #[test]
+    fn run_status() {
+        let content = "```\n```\n```no_run\n```\n```rust,compile_fail\n```\n```ignore\n```\n\
+                       ```text\n```\n```sh,ignore\n```\n";
+        assert_eq!(
+            scan_fences(content)
+                .iter()
+                .map(|f| f.run_status())
+                .collect::<Vec<_>>(),
+            vec![
+                RunStatus::Run,
+                RunStatus::Compile,
+                RunStatus::Compile,
+                RunStatus::Ignored,
+                RunStatus::NotRust,
+                RunStatus::NotRust,
+            ],
+        );
+    }
+
+    #[test]
+    fn modifiers() {
+        let mut info = String::new();
+        add_fence_modifier(&mut info, "no_run");
+        assert_eq!(info, "no_run");
+        add_fence_modifier(&mut info, "no_run");
+        assert_eq!(info, "no_run");
+        add_fence_modifier(&mut info, "ignore");
+        assert_eq!(info, "no_run,ignore");
+        remove_fence_modifier(&mut info, "no_run");
+        assert_eq!(info, "ignore");
+        remove_fence_modifier(&mut info, "ignore");
+        assert_eq!(info, "rust");
+        let mut info = "rust, ignore edition2018".to_owned();
+        remove_fence_modifier(&mut info, "ignore");
+        assert_eq!(info, "rust,edition2018");
+    }
+}
@@ -125,347 +125,4729 @@
 //! ``````
 //!
 //! Now each struct has doc which match itself!
+//!
+//! ## Listing the included files
+//!
+//! With the `manifest` feature, if the `DOC_COMMENT_MANIFEST` environment variable is set to a
+//! file path, the absolute path of each file included by the macros is appended to this file (one
+//! per line). Each line is written at once in append mode, so the crates being built in parallel
+//! don't mix their lines, but their order isn't specified and the same file can be listed
+//! multiple times. Since the compiler doesn't know that the macros depend on this variable, only
+//! the crates which are actually (re)built add their files: clean the build first to get a
+//! complete list.
 
+extern crate doc_comment_fences as fences;
 extern crate proc_macro;
 
+mod mdbook;
+mod template;
+
+use fences::{
+    add_fence_modifier, has_fence_modifier, is_rust_fence, lines_with_endings, number_lines,
+    remove_fence_modifier, rewrite_fences, scan_fences, RunStatus,
+};
 use proc_macro::token_stream::IntoIter as ProcIter;
-use proc_macro::{Delimiter, TokenStream, TokenTree};
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
 use std::fs;
 use std::iter::{FromIterator, Peekable};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-fn include_file(ident: &str, path: &Path, includes: &mut String) -> String {
-    let full_path = if !path.is_absolute() {
-        let p = Path::new(file!());
-        p.parent().unwrap().join(&path)
+/// The directories relative paths are resolved from, in order: the directory of the file calling
+/// the macro (if the compiler supports it), the directory of the crate being built and the
+/// directory of this file.
+fn base_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    dirs.extend(caller_dir());
+    dirs.extend(std::env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from));
+    dirs.push(Path::new(file!()).parent().unwrap().to_path_buf());
+    dirs
+}
+
+/// The home directory, from `HOME` (or `USERPROFILE` on Windows).
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+/// Replaces the leading `~/` of `path` with `home`, like shells do.
+fn expand_home(path: &Path, home: Option<PathBuf>) -> Result<PathBuf, String> {
+    let rest = match path.to_str() {
+        Some(p) if p.starts_with("~/") => &p[2..],
+        _ => return Ok(path.to_path_buf()),
+    };
+    match home {
+        Some(home) => Ok(home.join(rest)),
+        None => Err(format!(
+            "`{}` starts with `~/` but neither `HOME` nor `USERPROFILE` is set",
+            path.display()
+        )),
+    }
+}
+
+/// Returns the paths `path` can be resolved to, in order.
+fn candidate_paths(path: &Path) -> Vec<PathBuf> {
+    // The error is reported by `check_exists`.
+    let path = &expand_home(path, home_dir()).unwrap_or_else(|_| path.to_path_buf());
+    if path.is_absolute() {
+        return vec![path.to_path_buf()];
+    }
+    let mut paths = Vec::new();
+    for dir in base_dirs() {
+        let candidate = dir.join(path);
+        if !paths.contains(&candidate) {
+            paths.push(candidate);
+        }
+    }
+    paths
+}
+
+/// Relative paths are resolved from the first of `base_dirs` where the file exists. If it exists
+/// nowhere, the path from the first one is returned.
+fn resolve_path(path: &Path) -> PathBuf {
+    let candidates = candidate_paths(path);
+    match candidates.iter().find(|p| p.exists()) {
+        Some(found) => found.clone(),
+        None => candidates[0].clone(),
+    }
+}
+
+/// Returns the path given to `include_str!` (which is relative to the calling file) to track
+/// `path`. The absolute path is only used if the file was found from `CARGO_MANIFEST_DIR`, to keep
+/// the generated code readable.
+fn tracked_path(path: &Path) -> PathBuf {
+    let full_path = resolve_path(path);
+    if expand_home(path, home_dir()).as_ref().map(|p| p.as_path()) != Ok(path) {
+        return full_path;
+    }
+    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from);
+    let from_caller = caller_dir().map(|dir| dir.join(path)) == Some(full_path.clone());
+    match manifest_dir {
+        Some(ref dir) if !from_caller && dir.join(path) == full_path => full_path,
+        _ => path.to_path_buf(),
+    }
+}
+
+/// The error emitted when `path` cannot be found.
+fn not_found_message(path: &Path, tried: &[PathBuf]) -> String {
+    let mut msg = format!("`{}` doesn't exist, tried:", path.display());
+    for path in tried {
+        msg.push_str(&format!("\n  - `{}`", path.display()));
+    }
+    msg
+}
+
+/// Returns an error listing the tried paths if `path` cannot be found.
+fn check_exists(path: &Path, span: Span) -> Result<(), Error> {
+    expand_home(path, home_dir()).map_err(|e| Error::new(span, e))?;
+    let tried = candidate_paths(path);
+    if tried.iter().any(|p| p.exists()) {
+        return Ok(());
+    }
+    Err(Error::new(span, not_found_message(path, &tried)))
+}
+
+// Only compiled with a compiler supporting it (see `build.rs`).
+#[cfg(doc_comment_local_file)]
+#[allow(clippy::incompatible_msrv)]
+fn caller_dir() -> Option<PathBuf> {
+    let file = Span::call_site().local_file()?;
+    // The path is relative to the directory the compiler is run from.
+    Some(std::env::current_dir().ok()?.join(file.parent()?))
+}
+
+#[cfg(not(doc_comment_local_file))]
+fn caller_dir() -> Option<PathBuf> {
+    None
+}
+
+fn include_file(
+    ident: &str,
+    path: &Path,
+    span: Span,
+    includes: &mut String,
+) -> Result<String, Error> {
+    include_raw_file(ident, path, span, includes).map(|content| end_with_newline(&content))
+}
+
+/// Same as `include_file` but returns the file content as is.
+fn include_raw_file(
+    ident: &str,
+    path: &Path,
+    span: Span,
+    includes: &mut String,
+) -> Result<String, Error> {
+    check_exists(path, span)?;
+    let full_path = resolve_path(path);
+    check_not_dir(&full_path, span)?;
+    record_file(&full_path, span)?;
+    track_file(ident, &tracked_path(path), includes);
+    fs::read_to_string(&full_path).map_err(|e| {
+        Error::new(
+            span,
+            format!("Failed to read `{}`: {}", full_path.display(), e),
+        )
+    })
+}
+
+/// Makes `content` end with exactly one `\n` (unless it's empty). Without it, the closing marker
+/// of the last code block could be fused with what is added after the file.
+fn end_with_newline(content: &str) -> String {
+    let trimmed = content.trim_end_matches(|c| c == '\n' || c == '\r');
+    if trimmed.is_empty() {
+        return String::new();
+    }
+    format!("{}\n", trimmed)
+}
+
+/// Removes the blank lines at the start of `content` and the indentation of its first line.
+/// Otherwise, rustdoc could consider this line (and the following indented ones) as an indented
+/// code block and test it.
+fn start_at_column_zero(content: &str) -> &str {
+    content.trim_start_matches(|c| c == ' ' || c == '\t' || c == '\n' || c == '\r')
+}
+
+/// Same as `include_raw_file` but converts the file content from the given `encoding` to UTF-8.
+fn include_encoded_file(
+    path: &Path,
+    encoding: Encoding,
+    span: Span,
+    includes: &mut String,
+) -> Result<String, Error> {
+    check_exists(path, span)?;
+    let full_path = resolve_path(path);
+    check_not_dir(&full_path, span)?;
+    record_file(&full_path, span)?;
+    // `include_str!` would fail on a file which isn't valid UTF-8.
+    track_file("include_bytes", &tracked_path(path), includes);
+    match fs::read(&full_path) {
+        Ok(bytes) => Ok(encoding.decode(&bytes)),
+        Err(e) => Err(Error::new(
+            span,
+            format!("Failed to read `{}`: {}", full_path.display(), e),
+        )),
+    }
+}
+
+/// Returns the first line (starting at 1) which differs between `a` and `b`, line endings
+/// included.
+fn first_difference(a: &str, b: &str) -> Option<usize> {
+    let mut a_lines = a.split('\n');
+    let mut b_lines = b.split('\n');
+    let mut nb = 1;
+    loop {
+        match (a_lines.next(), b_lines.next()) {
+            (None, None) => return None,
+            (a, b) if a != b => return Some(nb),
+            _ => nb += 1,
+        }
+    }
+}
+
+/// Reading a directory gives an unhelpful io error, so we check it beforehand.
+fn check_not_dir(full_path: &Path, span: Span) -> Result<(), Error> {
+    if full_path.is_dir() {
+        return Err(Error::new(
+            span,
+            format!(
+                "`{}` is a directory but only files are supported (to test multiple files, use \
+                 `base_dir = \"...\"` with `files = [...]`)",
+                full_path.display()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// With the `manifest` feature, appends the absolute path of `full_path` to the file given by the
+/// `DOC_COMMENT_MANIFEST` environment variable (if it's set).
+#[cfg(feature = "manifest")]
+fn record_file(full_path: &Path, span: Span) -> Result<(), Error> {
+    append_to_manifest(full_path).map_err(|e| Error::new(span, e))
+}
+
+#[cfg(feature = "manifest")]
+fn append_to_manifest(full_path: &Path) -> Result<(), String> {
+    use std::io::Write;
+
+    let manifest = match std::env::var_os("DOC_COMMENT_MANIFEST") {
+        Some(manifest) => PathBuf::from(manifest),
+        None => return Ok(()),
+    };
+    let full_path = fs::canonicalize(full_path).unwrap_or_else(|_| full_path.to_path_buf());
+    // The line is written in one call so lines written at the same time don't get mixed.
+    let line = format!("{}\n", full_path.display());
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&manifest)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|e| {
+            format!(
+                "Failed to write to `{}` (from `DOC_COMMENT_MANIFEST`): {}",
+                manifest.display(),
+                e
+            )
+        })
+}
+
+#[cfg(not(feature = "manifest"))]
+fn record_file(_: &Path, _: Span) -> Result<(), Error> {
+    Ok(())
+}
+
+fn track_file(ident: &str, path: &Path, includes: &mut String) {
+    let ty = if ident == "include_bytes" {
+        "[u8]"
     } else {
-        path.to_path_buf()
+        "str"
     };
     // This part is to trigger recompilation in case the file has been updated!
     includes.push_str(&format!(
-        "const _: &'static str = {}!(\"{}\");",
+        "const _: &'static {} = {}!(\"{}\");",
+        ty,
         ident,
-        path.display()
+        escape_content(&path.display().to_string())
     ));
-    match fs::read_to_string(&full_path) {
-        Ok(s) => {
-            // Not the best way but whatever...
-            s.replace("\\", "\\\\").replace("\"", "\\\"")
+}
+
+/// Puts the items generated to track the included files (and the warnings) in a hidden module so
+/// they're easier to skip when reading the expanded code. It's in an anonymous constant so
+/// multiple `doctest` calls don't generate the same module.
+fn hide_tracking(includes: &str) -> String {
+    if includes.is_empty() {
+        return String::new();
+    }
+    format!(
+        "const _: () = {{ #[doc(hidden)] mod doc_comment_file_tracking {{ {} }} }};",
+        includes
+    )
+}
+
+/// Encodings supported by the `encoding` option of `doctest`.
+#[derive(Clone, Copy)]
+enum Encoding {
+    Latin1,
+    Windows1252,
+}
+
+/// Characters of the `0x80..=0x9F` range in Windows-1252 (unassigned bytes are kept as is).
+const WINDOWS_1252: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+impl Encoding {
+    fn from_name(name: &str) -> Option<Encoding> {
+        match name.to_ascii_lowercase().as_str() {
+            "latin1" | "latin-1" | "iso-8859-1" => Some(Encoding::Latin1),
+            "windows-1252" | "cp1252" => Some(Encoding::Windows1252),
+            _ => None,
         }
-        Err(e) => panic!("Failed to read `{}`: {}", full_path.display(), e),
+    }
+
+    fn decode(self, bytes: &[u8]) -> String {
+        bytes
+            .iter()
+            .map(|&b| match (self, b) {
+                (Encoding::Windows1252, 0x80..=0x9F) => WINDOWS_1252[b as usize - 0x80],
+                _ => b as char,
+            })
+            .collect()
     }
 }
 
-fn parse_macro_call(
-    ident: String,
-    attrs: &mut Peekable<ProcIter>,
-    out: &mut String,
-    includes: &mut String,
-) {
-    if ident != "include_str" {
-        panic!(
-            "Unsupported macro call `{}` in proc_macro (only `include_str` is currently supported)",
-            ident
-        );
+/// The item the documentation generated by `doctest` is put on.
+#[derive(Clone, Copy, PartialEq)]
+enum Holder {
+    Mod,
+    Fn,
+    Struct,
+    Extern,
+}
+
+impl Holder {
+    fn from_name(name: &str) -> Option<Holder> {
+        match name {
+            "mod" => Some(Holder::Mod),
+            "fn" => Some(Holder::Fn),
+            "struct" => Some(Holder::Struct),
+            "extern" => Some(Holder::Extern),
+            _ => None,
+        }
     }
-    // First we remove the "!" token
-    attrs.next();
-    match attrs.next() {
-        Some(TokenTree::Group(g)) => {
-            for token in g.stream().into_iter() {
-                match token {
-                    TokenTree::Literal(l) => {
-                        let l_s = l.to_string();
-                        if !l_s.starts_with('"') {
-                            panic!("`{}` should be a string literal!", l_s);
-                        }
-                        let l_s = &l_s[1..l_s.len() - 1];
-                        let path = Path::new(&l_s);
-                        out.push_str(&include_file(&ident, &path, includes));
-                    }
-                    TokenTree::Punct(ref p) if p.to_string() == "," => {}
-                    x => panic!("Unexpected item `{}` in macro call `{}`", x, ident),
-                }
-            }
+
+    /// The generated code is concatenated rather than built with `format!` so there is no brace
+    /// to escape.
+    fn item(self, name: &str) -> String {
+        match self {
+            Holder::Mod => ["mod ", name, " {}"].concat(),
+            Holder::Fn => ["#[allow(dead_code)] fn ", name, "() {}"].concat(),
+            Holder::Struct => [
+                "#[allow(dead_code, non_camel_case_types)] struct ",
+                name,
+                ";",
+            ]
+            .concat(),
+            Holder::Extern => "extern {}".to_owned(),
         }
-        Some(x) => panic!("Unexpected `{}` in macro `{}`", x, ident),
-        None => panic!("Expected item in macro `{}`, found nothing...", ident),
     }
 }
 
-fn parse_attr(attrs: TokenStream, includes: &mut String, is_inner: bool) -> String {
-    let mut out = format!("#{}[doc = \"", if is_inner { "!" } else { "" });
-    let mut attrs = attrs.into_iter().peekable();
-    loop {
-        let attr = match attrs.next() {
-            Some(a) => a,
-            None => break,
-        };
-        match attr {
-            TokenTree::Literal(l) => {
-                let print = l.to_string();
-                if print.starts_with("b") {
-                    out.push_str(&print[2..print.len() - 1]);
-                } else if print.starts_with('\'') {
-                    out.push_str(&print[1..print.len() - 1]);
-                } else if print.starts_with('"') {
-                    out.push_str(&print[1..print.len() - 1]);
-                } else {
-                    out.push_str(&print);
+/// Unescapes the content of a string literal the same way rustc does, returning `None` if the
+/// literal is invalid.
+fn unescape(s: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\r' => return None,
+            '\\' => match chars.next()? {
+                '\\' => out.push('\\'),
+                '"' => out.push('"'),
+                '\'' => out.push('\''),
+                'n' => out.push('\n'),
+                'r' => out.push('\r'),
+                't' => out.push('\t'),
+                '0' => out.push('\0'),
+                'x' => {
+                    let code = chars.by_ref().take(2).collect::<String>();
+                    match u8::from_str_radix(&code, 16) {
+                        Ok(b) if code.len() == 2 && b < 0x80 => out.push(b as char),
+                        _ => return None,
+                    }
                 }
-            }
-            TokenTree::Punct(ref p) if p.to_string() == "," => {}
-            TokenTree::Ident(i) => {
-                if attrs.peek().map(|a| a.to_string() == "!") == Some(true) {
-                    parse_macro_call(i.to_string(), &mut attrs, &mut out, includes);
-                } else {
-                    out.push_str(&i.to_string());
+                'u' => {
+                    if chars.next()? != '{' {
+                        return None;
+                    }
+                    let code = chars
+                        .by_ref()
+                        .take_while(|&c| c != '}')
+                        .filter(|&c| c != '_')
+                        .collect::<String>();
+                    out.push(std::char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
                 }
-            }
-            TokenTree::Group(ref g) if g.delimiter() == Delimiter::None => {
-                // In case we give an ident through a macro, we need to read it from here. However,
-                // if it contains more than just one ident, then it's something else and we need to
-                // panic.
-                let mut it = g.stream().into_iter();
-                match it.next() {
-                    Some(TokenTree::Ident(i)) => {
-                        if it.next().is_some() {
-                            panic!("This token isn't supported: {:?}", g)
-                        }
-                        out.push_str(&i.to_string());
+                // A `\` at the end of a line skips the line break and the following whitespace.
+                '\n' => {
+                    while chars.peek().map(|c| c.is_whitespace()) == Some(true) {
+                        chars.next();
                     }
-                    _ => panic!("This token isn't supported: {:?}", g),
                 }
+                _ => return None,
+            },
+            c => out.push(c),
+        }
+    }
+    Some(out)
+}
+
+fn escape_content(s: &str) -> String {
+    // Not the best way but whatever...
+    s.replace("\\", "\\\\")
+        .replace("\"", "\\\"")
+        // Bare CRs aren't allowed in string literals.
+        .replace("\r", "\\r")
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Wraps `body` into a hidden `fn main() -> ty`, adding a hidden `Ok(())` at the end unless the
+/// last line already looks like it returns something.
+fn wrap_main(body: &str, ty: &str) -> String {
+    let mut out = format!("# fn main() -> {} {{\n", ty);
+    out.push_str(body);
+    if !body.is_empty() && !body.ends_with('\n') {
+        out.push('\n');
+    }
+    let last = body
+        .lines()
+        .map(|l| l.trim())
+        .map(|l| {
+            if l.starts_with("# ") {
+                l[2..].trim()
+            } else {
+                l
             }
-            x => {
-                panic!("This token isn't supported: {:?}", x);
+        })
+        .rfind(|l| !l.is_empty() && *l != "#")
+        .unwrap_or("");
+    if returns_result(ty) && !last.starts_with("Ok") && !last.starts_with("return ") {
+        out.push_str("# Ok(())\n");
+    }
+    out.push_str("# }\n");
+    out
+}
+
+/// Returns `true` if `ty` looks like a `Result` (like `io::Result<()>`), unlike `ExitCode` for
+/// example.
+fn returns_result(ty: &str) -> bool {
+    let name = ty.split('<').next().unwrap_or("").trim();
+    name.rsplit("::").next().unwrap_or("").ends_with("Result")
+}
+
+/// Makes `body` a `#![no_std]` crate, unless it already is. `std` is still linked (the test
+/// needs it to run) but it isn't in the prelude anymore. Since rustdoc doesn't add a `fn main` to
+/// `#![no_std]` code, it's added here too.
+fn add_no_std(body: &str) -> String {
+    if body.contains("#![no_std]") {
+        return body.to_owned();
+    }
+    let mut out = "# #![no_std]\n# extern crate alloc;\n# extern crate std;\n".to_owned();
+    if body.contains("fn main") {
+        out.push_str(body);
+        return out;
+    }
+    out.push_str("# fn main() {\n");
+    out.push_str(body);
+    if !body.is_empty() && !body.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str("# }\n");
+    out
+}
+
+/// Adds a hidden `extern crate krate;` at the top of `body`, unless it already has one.
+fn add_extern_crate(body: &str, krate: &str) -> String {
+    let has_extern_crate = body.lines().any(|line| {
+        let line = line.trim_start().trim_start_matches('#').trim_start();
+        line.starts_with("extern crate") && uses_crate(line, krate)
+    });
+    if has_extern_crate {
+        return body.to_owned();
+    }
+    format!("# extern crate {};\n{}", krate, body)
+}
+
+/// Adds the given modifiers to all the rust code blocks of `content`, unless they already have
+/// them.
+fn add_block_attrs<S: AsRef<str>>(content: &str, modifiers: &[S]) -> String {
+    rewrite_fences(content, |info, _| {
+        if is_rust_fence(info) {
+            for modifier in modifiers {
+                add_fence_modifier(info, modifier.as_ref());
             }
         }
+    })
+}
+
+/// Adds `edition` to the rust code blocks which don't have an edition yet.
+fn set_edition(content: &str, edition: &str) -> String {
+    rewrite_fences(content, |info, _| {
+        let has_edition = info
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .any(|m| m.starts_with("edition"));
+        if is_rust_fence(info) && !has_edition {
+            add_fence_modifier(info, edition);
+        }
+    })
+}
+
+/// Returns `path` without its first `count` components. The file name is always kept.
+fn strip_components(path: &Path, count: usize) -> String {
+    let components = path.components().collect::<Vec<_>>();
+    let count = count.min(components.len().saturating_sub(1));
+    components[count..]
+        .iter()
+        .collect::<PathBuf>()
+        .display()
+        .to_string()
+}
+
+/// Adds a hidden `// source: path:line` comment at the beginning of each rust code block,
+/// `line` being the line of the first line of the code block.
+fn add_source_markers(content: &str, path: &Path) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut pos = 0;
+    for fence in scan_fences(content)
+        .iter()
+        .filter(|f| is_rust_fence(f.info))
+    {
+        out.push_str(&content[pos..fence.body_range.start]);
+        if !content[..fence.body_range.start].ends_with('\n') {
+            out.push('\n');
+        }
+        out.push_str(&format!(
+            "# // source: {}:{}\n",
+            path.display(),
+            fence.line + 1
+        ));
+        pos = fence.body_range.start;
     }
-    out.push_str("\"]");
+    out.push_str(&content[pos..]);
     out
 }
 
-fn next_is_ident(parts: &mut Peekable<ProcIter>) -> bool {
-    match parts.peek() {
-        Some(TokenTree::Ident(_)) => true,
-        _ => false,
+/// Adds `modifier` to the rust code blocks of `content` which have the `target` modifier.
+fn add_modifier_to(content: &str, modifier: &str, target: &str) -> String {
+    rewrite_fences(content, |info, _| {
+        if is_rust_fence(info) && has_fence_modifier(info, target) {
+            add_fence_modifier(info, modifier);
+        }
+    })
+}
+
+/// Removes the trailing whitespace of each line and the empty lines around the code so code
+/// blocks only differing by them are considered identical.
+fn normalize_body(body: &str) -> String {
+    body.lines()
+        .map(|l| l.trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_matches('\n')
+        .to_owned()
+}
+
+/// Removes the lines rustdoc hides when rendering a rust code block (`# ...` and `#`) and turns
+/// `##` back into `#`.
+fn strip_hidden_lines(body: &str) -> String {
+    let mut out = String::with_capacity(body.len());
+    for line in fences::lines_with_endings(body) {
+        let indent = line.len() - line.trim_start().len();
+        let code = line[indent..].trim_end_matches(|c| c == '\n' || c == '\r');
+        if code == "#" || code.starts_with("# ") || code.starts_with("#\t") {
+            continue;
+        }
+        out.push_str(&line[..indent]);
+        if code.starts_with("##") {
+            out.push_str(&line[indent + 1..]);
+        } else {
+            out.push_str(&line[indent..]);
+        }
     }
+    out
 }
 
-fn parse_item(mut parts: Peekable<ProcIter>, includes: &mut String) -> String {
-    let mut out = String::new();
-    loop {
-        let attr = match parts.next() {
-            Some(a) => a,
-            None => break,
-        };
-        match attr {
-            TokenTree::Group(g) => {
-                out.push_str(match g.delimiter() {
-                    Delimiter::Parenthesis => "(",
-                    Delimiter::Brace => "{",
-                    Delimiter::Bracket => "[",
-                    Delimiter::None => "",
-                });
-                out.push_str(&parse_item(g.stream().into_iter().peekable(), includes));
-                out.push_str(match g.delimiter() {
-                    Delimiter::Parenthesis => ")",
-                    Delimiter::Brace => "}",
-                    Delimiter::Bracket => "]",
-                    Delimiter::None => "",
-                });
+/// Returns the code rustdoc compiles for a rust code block: the hidden lines are kept, without
+/// their `# ` prefix.
+fn unhide_lines(body: &str) -> String {
+    let mut out = String::with_capacity(body.len());
+    for line in fences::lines_with_endings(body) {
+        let indent = line.len() - line.trim_start().len();
+        let code = &line[indent..];
+        out.push_str(&line[..indent]);
+        if code.starts_with("# ") || code.starts_with("#\t") {
+            out.push_str(&code[2..]);
+        } else if code.starts_with("##") || code.trim_end() == "#" {
+            out.push_str(&code[1..]);
+        } else {
+            out.push_str(code);
+        }
+    }
+    out
+}
+
+/// Returns the index of the first line of `body` whose indentation mixes tabs and spaces. Blank
+/// lines are skipped since their whitespace isn't indentation.
+fn mixed_indent_line(body: &str) -> Option<usize> {
+    body.lines().position(|line| {
+        let indent = &line[..line.len() - line.trim_start().len()];
+        indent.len() != line.len() && indent.contains('\t') && indent.contains(' ')
+    })
+}
+
+/// Returns the line and the target of the links of `content` (outside of code) which point to a
+/// relative path, like `[setup](setup.md#install)` (the anchor is removed). Links with a scheme
+/// (`https:`, `mailto:`...), absolute paths and anchors alone are skipped.
+fn relative_links(content: &str) -> Vec<(usize, String)> {
+    let fences = scan_fences(content);
+    let mut links = Vec::new();
+    for (nb, line) in content.lines().enumerate() {
+        let nb = nb + 1;
+        if fences.iter().any(|f| f.line <= nb && nb <= f.end_line) {
+            continue;
+        }
+        // What is in inline code isn't a link.
+        let text = line.split('`').step_by(2).collect::<Vec<_>>().join(" ");
+        let mut rest = text.as_str();
+        while let Some(pos) = rest.find("](") {
+            rest = &rest[pos + 2..];
+            let end = rest.find(')').unwrap_or(rest.len());
+            // The destination can be followed by a title: `[a](b.md "title")`, or be between `<>`
+            // if it contains spaces.
+            let target = rest[..end].trim_start();
+            let target = if target.starts_with('<') {
+                target[1..].split('>').next().unwrap_or("")
+            } else {
+                target.split_whitespace().next().unwrap_or("")
+            };
+            let target = target.split(|c| c == '#' || c == '?').next().unwrap_or("");
+            if !target.is_empty() && !target.contains(':') && !target.starts_with('/') {
+                links.push((nb, target.to_owned()));
             }
-            TokenTree::Punct(ref x) if x.to_string() == "#" => match parts.peek() {
-                Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Bracket => {
-                    let stream = g.stream();
-                    let mut sub_parts = stream.into_iter();
-                    match sub_parts.next() {
-                        Some(TokenTree::Ident(ref i)) if i.to_string() == "doc_comment" => {}
-                        _ => {
-                            out.push_str(&x.to_string());
-                            continue;
-                        }
+            rest = &rest[end..];
+        }
+    }
+    links
+}
+
+/// Removes the indentation common to the lines of `doc`, except the first one which starts right
+/// after the opening quote so it's only trimmed. Blank lines don't count.
+fn dedent(doc: &str) -> String {
+    let unindented = |line: &str| line.trim_start_matches(|c| c == ' ' || c == '\t').len();
+    let indent_len = |line: &str| line.len() - unindented(line);
+    let indent = doc
+        .lines()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .map(indent_len)
+        .min()
+        .unwrap_or(0);
+    let mut out = String::with_capacity(doc.len());
+    for (nb, line) in lines_with_endings(doc).enumerate() {
+        if nb == 0 {
+            out.push_str(&line[indent_len(line)..]);
+        } else {
+            out.push_str(&line[std::cmp::min(indent, indent_len(line))..]);
+        }
+    }
+    out
+}
+
+/// Checks that `code` can be tokenized: the delimiters are balanced and the string literals and
+/// block comments are closed. `TokenStream::from_str` cannot be used for it since the compiler
+/// doesn't handle the errors it emits in proc-macros well. Returns the line (`code` starting on
+/// `first_line`) and the description of the first problem.
+fn check_tokens(code: &str, first_line: usize) -> Result<(), (usize, String)> {
+    let chars = code.chars().collect::<Vec<_>>();
+    let is_ident_char = |c: char| c.is_alphanumeric() || c == '_';
+    let mut delimiters: Vec<(char, usize)> = Vec::new();
+    let mut line = first_line;
+    let mut pos = 0;
+    // Moves `pos` to right after `end`, returns `false` if `end` isn't found.
+    let skip_to = |pos: &mut usize, line: &mut usize, end: &str, escapes: bool| {
+        let end = end.chars().collect::<Vec<_>>();
+        while *pos < chars.len() {
+            if chars[*pos..].starts_with(&end) {
+                *pos += end.len();
+                return true;
+            }
+            if chars[*pos] == '\n' {
+                *line += 1;
+            }
+            if escapes && chars[*pos] == '\\' {
+                *pos += 1;
+                if chars.get(*pos) == Some(&'\n') {
+                    *line += 1;
+                }
+            }
+            *pos += 1;
+        }
+        false
+    };
+    while pos < chars.len() {
+        let c = chars[pos];
+        let start_line = line;
+        let after_ident = pos > 0 && is_ident_char(chars[pos - 1]);
+        pos += 1;
+        match c {
+            '\n' => line += 1,
+            '/' if chars.get(pos) == Some(&'/') => {
+                while pos < chars.len() && chars[pos] != '\n' {
+                    pos += 1;
+                }
+            }
+            '/' if chars.get(pos) == Some(&'*') => {
+                pos += 1;
+                let mut depth = 1;
+                while depth > 0 {
+                    if pos >= chars.len() {
+                        return Err((start_line, "unclosed block comment".to_owned()));
                     }
-                    match sub_parts.next() {
-                        Some(TokenTree::Group(g)) => {
-                            out.push_str(&parse_attr(g.stream(), includes, false));
-                            parts.next();
-                        }
-                        _ => {
-                            out.push_str(&x.to_string());
-                        }
+                    if chars[pos..].starts_with(&['/', '*']) {
+                        depth += 1;
+                        pos += 1;
+                    } else if chars[pos..].starts_with(&['*', '/']) {
+                        depth -= 1;
+                        pos += 1;
+                    } else if chars[pos] == '\n' {
+                        line += 1;
                     }
+                    pos += 1;
                 }
-                _ => {
-                    out.push_str(&x.to_string());
+            }
+            // `skip_to` moves `pos` after the literal when it's closed.
+            '"' if !skip_to(&mut pos, &mut line, "\"", true) => {
+                return Err((start_line, "unclosed string literal".to_owned()));
+            }
+            'r' | 'b' if !after_ident => {
+                let mut raw = pos;
+                if c == 'b' && chars.get(raw) == Some(&'r') {
+                    raw += 1;
+                } else if c == 'b' {
+                    continue;
                 }
-            },
-            TokenTree::Ident(i) => {
-                out.push_str(&i.to_string());
-                if next_is_ident(&mut parts) {
-                    out.push_str(" ");
+                let hashes = chars[raw..].iter().take_while(|&&c| c == '#').count();
+                if chars.get(raw + hashes) != Some(&'"') {
+                    continue;
+                }
+                pos = raw + hashes + 1;
+                let end = format!("\"{}", "#".repeat(hashes));
+                if !skip_to(&mut pos, &mut line, &end, false) {
+                    return Err((start_line, "unclosed raw string literal".to_owned()));
                 }
             }
-            x => {
-                out.push_str(&x.to_string());
+            '\'' => {
+                // `skip_to` reads the escape, so `'\\'` isn't closed by its second backslash.
+                if chars.get(pos) == Some(&'\\') {
+                    if !skip_to(&mut pos, &mut line, "'", true) {
+                        return Err((start_line, "unclosed character literal".to_owned()));
+                    }
+                } else if chars.get(pos + 1) == Some(&'\'') {
+                    pos += 2;
+                }
+                // Otherwise it's a lifetime or a label.
+            }
+            '(' | '[' | '{' => delimiters.push((c, line)),
+            ')' | ']' | '}' => {
+                let open = match c {
+                    ')' => '(',
+                    ']' => '[',
+                    _ => '{',
+                };
+                match delimiters.pop() {
+                    Some((o, _)) if o == open => {}
+                    Some((o, o_line)) => {
+                        return Err((
+                            line,
+                            format!("mismatched `{}` (`{}` opened on line {})", c, o, o_line),
+                        ))
+                    }
+                    None => return Err((line, format!("unexpected `{}`", c))),
+                }
+            }
+            _ => {}
+        }
+    }
+    match delimiters.pop() {
+        Some((open, open_line)) => Err((open_line, format!("unclosed `{}`", open))),
+        None => Ok(()),
+    }
+}
+
+/// Removes the `//` comments of a rust code block (but not the `///` and `//!` doc comments). The
+/// lines which only contain a comment are removed.
+fn strip_comments(body: &str) -> String {
+    let mut out = String::with_capacity(body.len());
+    for line in fences::lines_with_endings(body) {
+        let code = line.trim_end_matches(|c| c == '\n' || c == '\r');
+        let start = match comment_start(code) {
+            Some(start) => start,
+            None => {
+                out.push_str(line);
+                continue;
             }
+        };
+        let kept = code[..start].trim_end();
+        if kept.is_empty() {
+            continue;
         }
+        out.push_str(kept);
+        out.push_str(&line[code.len()..]);
     }
     out
 }
 
-// fn check_if_is_inner(item: &mut Peekable<ProcIter>) -> bool {
-//     match item.peek() {
-//         Some(TokenTree::Ident(i)) => {
-//             i.to_string().is_empty() || format!("{:?}", i.span()) == "#0 bytes(0..0)"
-//         }
+/// Returns the position of the `//` comment of `line`, ignoring the ones in string (raw or not)
+/// and character literals and the doc comments.
+fn comment_start(line: &str) -> Option<usize> {
+    let chars = line.char_indices().collect::<Vec<_>>();
+    let is_ident = |i: usize| {
+        chars
+            .get(i)
+            .map(|&(_, c)| c.is_alphanumeric() || c == '_')
+            .unwrap_or(false)
+    };
+    let mut i = 0;
+    while i < chars.len() {
+        let (pos, c) = chars[i];
+        match c {
+            '"' => {
+                i += 1;
+                while i < chars.len() && chars[i].1 != '"' {
+                    i += if chars[i].1 == '\\' { 2 } else { 1 };
+                }
+            }
+            // `r"..."`, `r#"..."#` and `br"..."`, which have no escapes.
+            'r' if i == 0
+                || !is_ident(i - 1)
+                || (chars[i - 1].1 == 'b' && (i == 1 || !is_ident(i - 2))) =>
+            {
+                let hashes = line[pos + 1..].len() - line[pos + 1..].trim_start_matches('#').len();
+                if line[pos + 1 + hashes..].starts_with('"') {
+                    let closing = format!("\"{}", "#".repeat(hashes));
+                    let start = pos + 2 + hashes;
+                    match line[start..].find(&closing) {
+                        Some(end) => {
+                            let end = start + end + closing.len();
+                            while i < chars.len() && chars[i].0 < end {
+                                i += 1;
+                            }
+                            continue;
+                        }
+                        None => return None,
+                    }
+                }
+            }
+            '\'' => {
+                if chars.get(i + 1).map(|&(_, c)| c) == Some('\\') {
+                    i += 3;
+                    while i < chars.len() && chars[i].1 != '\'' {
+                        i += 1;
+                    }
+                } else if chars.get(i + 2).map(|&(_, c)| c) == Some('\'') {
+                    i += 2;
+                }
+            }
+            '/' if line[pos..].starts_with("//") => {
+                let rest = &line[pos + 2..];
+                if (rest.starts_with('/') && !rest.starts_with("//")) || rest.starts_with('!') {
+                    return None;
+                }
+                return Some(pos);
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Replaces tabs in `line` with spaces, up to the next multiple of `tab_width`.
+fn expand_tabs(line: &str, tab_width: usize) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        match c {
+            '\t' => {
+                let spaces = tab_width - column % tab_width;
+                out.extend(std::iter::repeat(' ').take(spaces));
+                column += spaces;
+            }
+            '\n' => {
+                out.push(c);
+                column = 0;
+            }
+            c => {
+                out.push(c);
+                column += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Generates a `compile_error!` with the given message pointing to `span`.
+fn emit_error(span: Span, msg: &str) -> TokenStream {
+    let mut lit = Literal::string(msg);
+    lit.set_span(span);
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+    let mut group = Group::new(
+        Delimiter::Parenthesis,
+        TokenStream::from_iter(vec![TokenTree::Literal(lit)]),
+    );
+    group.set_span(span);
+    let mut semi = Punct::new(';', Spacing::Alone);
+    semi.set_span(span);
+    TokenStream::from_iter(vec![
+        TokenTree::Ident(Ident::new("compile_error", span)),
+        TokenTree::Punct(bang),
+        TokenTree::Group(group),
+        TokenTree::Punct(semi),
+    ])
+}
+
+/// An error reported to the user with `compile_error!`.
+struct Error {
+    span: Span,
+    msg: String,
+}
+
+impl Error {
+    fn new(span: Span, msg: String) -> Error {
+        Error { span, msg }
+    }
+
+    /// Creates an error when there is no better span available.
+    fn call_site(msg: String) -> Error {
+        Error::new(Span::call_site(), msg)
+    }
+
+    fn into_compile_error(self) -> TokenStream {
+        emit_error(self.span, &self.msg)
+    }
+}
+
+/// Parses the code generated by the macros. It's not supposed to fail but if it does (because of
+/// an escaping bug for example), showing what was generated is more helpful than a panic. Recent
+/// compilers panic themselves on lexing errors though, so it only helps with the older ones.
+fn parse_generated(code: &str) -> Result<TokenStream, Error> {
+    TokenStream::from_str(code).map_err(|_| {
+        let mut preview = code.chars().take(200).collect::<String>();
+        if preview.len() < code.len() {
+            preview.push_str("...");
+        }
+        Error::call_site(format!(
+            "doc-comment generated invalid code, please report it as a bug: `{}`",
+            preview
+        ))
+    })
+}
+
+/// Turns `s` into something usable as an identifier by replacing all non-alphanumeric characters
+/// with `_`.
+fn sanitize_ident(s: &str) -> String {
+    let ident = s
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    let ident = ident.trim_matches('_');
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", ident)
+    } else {
+        ident.to_owned()
+    }
+}
+
+/// Returns the file stem of `path` as a (lowercase) identifier, like `quick_start` for
+/// `docs/Quick-Start.md`.
+fn stem_ident(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    sanitize_ident(&stem).to_lowercase()
+}
+
+fn is_keyword(s: &str) -> bool {
+    match s {
+        "_" | "as" | "break" | "const" | "continue" | "crate" | "else" | "enum" | "extern"
+        | "false" | "fn" | "for" | "if" | "impl" | "in" | "let" | "loop" | "match" | "mod"
+        | "move" | "mut" | "pub" | "ref" | "return" | "self" | "Self" | "static" | "struct"
+        | "super" | "trait" | "true" | "type" | "unsafe" | "use" | "where" | "while" | "async"
+        | "await" | "dyn" | "abstract" | "become" | "box" | "do" | "final" | "macro"
+        | "override" | "priv" | "typeof" | "unsized" | "virtual" | "yield" | "try" => true,
+        _ => false,
+    }
+}
+
+/// Returns the markdown contained in the `//!` and `///` comments of a rust source file. All the
+/// other lines are ignored, or replaced with empty lines if `preserve_lines` is `true`.
+fn extract_doc_comments(source: &str, preserve_lines: bool) -> String {
+    let mut out = String::new();
+    for line in source.lines() {
+        let line = line.trim_start();
+        let is_doc =
+            line.starts_with("//!") || (line.starts_with("///") && !line.starts_with("////"));
+        if !is_doc {
+            if preserve_lines {
+                out.push('\n');
+            }
+            continue;
+        }
+        let doc = &line[3..];
+        out.push_str(if doc.starts_with(' ') { &doc[1..] } else { doc });
+        out.push('\n');
+    }
+    out
+}
+
+/// Returns the name of the item declared on `line`, or `impl` for an `impl` block.
+fn item_name(line: &str) -> Option<String> {
+    let mut words = line
+        .split(|c: char| c.is_whitespace() || c == '<' || c == '(' || c == ':' || c == '{')
+        .filter(|w| !w.is_empty() && !w.starts_with("crate)") && !w.starts_with("super)"));
+    while let Some(word) = words.next() {
+        match word {
+            "pub" | "unsafe" | "async" | "default" | "extern" | "auto" => {}
+            w if w.starts_with('"') => {}
+            "impl" => return Some("impl".to_owned()),
+            "const" | "fn" | "struct" | "enum" | "union" | "trait" | "mod" | "static" | "type"
+            | "macro_rules!" => {
+                let name = match words.next() {
+                    Some("fn") if word == "const" => words.next(),
+                    Some("mut") if word == "static" => words.next(),
+                    name => name,
+                };
+                return name.map(|name| {
+                    name.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '_')
+                        .to_owned()
+                });
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// Returns the markdown of each documented item of a rust source file, along with its name, in
+/// order. The `//!` comments are returned as a `crate` item.
+fn extract_item_docs(source: &str) -> Vec<(String, String)> {
+    let mut items = Vec::new();
+    // The `///` comments waiting for their item.
+    let mut pending = String::new();
+    let mut inner = String::new();
+    for line in source.lines() {
+        let line = line.trim_start();
+        if line.starts_with("//!") {
+            inner.push_str(&extract_doc_comments(line, false));
+            continue;
+        }
+        if !inner.is_empty() {
+            items.push((
+                "crate".to_owned(),
+                std::mem::replace(&mut inner, String::new()),
+            ));
+        }
+        if line.starts_with("///") && !line.starts_with("////") {
+            pending.push_str(&extract_doc_comments(line, false));
+        } else if !pending.is_empty()
+            && !line.is_empty()
+            && !line.starts_with("#[")
+            && !line.starts_with("//")
+        {
+            let name = item_name(line).unwrap_or_else(|| "item".to_owned());
+            items.push((name, std::mem::replace(&mut pending, String::new())));
+        }
+    }
+    if !inner.is_empty() {
+        items.push(("crate".to_owned(), inner));
+    }
+    items
+}
+
+/// Removes the YAML (`---`) or TOML (`+++`) front matter at the beginning of `content`. If
+/// `preserve_lines` is `true`, it's replaced with empty lines instead.
+fn strip_front_matter(content: &str, preserve_lines: bool) -> String {
+    let mut lines = fences::lines_with_endings(content);
+    let first = match lines.next() {
+        Some(l) if l.trim_end() == "---" || l.trim_end() == "+++" => l,
+        _ => return content.to_owned(),
+    };
+    let mut end = first.len();
+    let mut nb_lines = 1;
+    for line in lines {
+        end += line.len();
+        nb_lines += 1;
+        if line.trim_end() == first.trim_end() {
+            let mut out = String::new();
+            if preserve_lines {
+                out.push_str(&"\n".repeat(nb_lines));
+            }
+            out.push_str(&content[end..]);
+            return out;
+        }
+    }
+    // Not closed so it's not a front matter.
+    content.to_owned()
+}
+
+/// Parses the `start..end` (or `start..=end`) line ranges of a `ranges_from` file, one per line.
+/// Lines start at 1. The returned ranges are exclusive.
+fn parse_ranges(content: &str) -> Result<Vec<(usize, usize)>, String> {
+    let mut ranges = Vec::new();
+    for (nb, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || {
+            format!(
+                "line {}: invalid range `{}` (expected `start..end` or `start..=end`)",
+                nb + 1,
+                line
+            )
+        };
+        ranges.push(parse_range(line).ok_or_else(invalid)?);
+    }
+    Ok(ranges)
+}
+
+/// Parses a `start..end` (or `start..=end`) line range into an exclusive range.
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let pos = range.find("..")?;
+    let start = range[..pos].trim().parse::<usize>().ok()?;
+    let end = &range[pos + 2..];
+    let end = if end.starts_with('=') {
+        end[1..].trim().parse::<usize>().ok()? + 1
+    } else {
+        end.trim().parse::<usize>().ok()?
+    };
+    if start == 0 || start > end {
+        return None;
+    }
+    Some((start, end))
+}
+
+/// Parses the comma-separated lines and line ranges of `hide_lines` (like `1..3,10`).
+fn parse_line_list(list: &str) -> Result<Vec<(usize, usize)>, String> {
+    list.split(',')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            let range = match item.parse::<usize>() {
+                Ok(line) if line > 0 => Some((line, line + 1)),
+                Ok(_) => None,
+                Err(_) => parse_range(item),
+            };
+            range.ok_or_else(|| {
+                format!(
+                    "invalid line `{}` (expected `line`, `start..end` or `start..=end`)",
+                    item
+                )
+            })
+        })
+        .collect()
+}
+
+/// Prefixes the lines in `ranges` with `# ` so rustdoc hides them.
+fn hide_lines(content: &str, ranges: &[(usize, usize)]) -> String {
+    let mut out = String::with_capacity(content.len());
+    for (nb, line) in fences::lines_with_endings(content).enumerate() {
+        let nb = nb + 1;
+        if ranges.iter().any(|&(start, end)| start <= nb && nb < end) {
+            if line.trim().is_empty() {
+                out.push('#');
+                out.push_str(&line[line.trim_end_matches(|c| c == '\n' || c == '\r').len()..]);
+                continue;
+            }
+            out.push_str("# ");
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+/// Puts `content` in a code block with the given info string. The fence is longer than the
+/// backtick fences `content` may contain so they don't close it.
+fn wrap_in_fence(content: &str, info: &str) -> String {
+    let longest = content
+        .lines()
+        .map(|line| {
+            let line = line.trim_start();
+            line.len() - line.trim_start_matches('`').len()
+        })
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(std::cmp::max(3, longest + 1));
+    let mut out = format!("{}{}\n{}", fence, info, content);
+    if !content.is_empty() && !content.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(&fence);
+    out.push('\n');
+    out
+}
+
+/// Replaces the lines which aren't in any of the `ranges` with empty lines, so the line numbers
+/// stay the same.
+fn keep_lines(content: &str, ranges: &[(usize, usize)]) -> String {
+    let mut out = String::with_capacity(content.len());
+    for (nb, line) in fences::lines_with_endings(content).enumerate() {
+        let nb = nb + 1;
+        if ranges.iter().any(|&(start, end)| start <= nb && nb < end) {
+            out.push_str(line);
+        } else if line.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// If `line` is a `<!-- <name>:start -->` or `<!-- <name>:end -->` comment, returns `true` for the
+/// first one and `false` for the second one.
+fn region_marker(line: &str, name: &str) -> Option<bool> {
+    let line = line.trim();
+    if !line.starts_with("<!--") || !line.ends_with("-->") || line.len() < 7 {
+        return None;
+    }
+    let inner = line[4..line.len() - 3].trim();
+    if !inner.starts_with(name) || !inner[name.len()..].starts_with(':') {
+        return None;
+    }
+    match &inner[name.len() + 1..] {
+        "start" => Some(true),
+        "end" => Some(false),
+        _ => None,
+    }
+}
+
+/// Returns the content between the `<!-- <name>:start -->` and `<!-- <name>:end -->` comments,
+/// separated with empty lines.
+fn extract_regions(content: &str, name: &str) -> Result<String, String> {
+    let mut out = String::new();
+    let mut start = None;
+    for (nb, line) in fences::lines_with_endings(content).enumerate() {
+        match (region_marker(line, name), start) {
+            (Some(true), Some(start)) => {
+                return Err(format!(
+                    "`<!-- {}:start -->` on line {} but the one on line {} isn't closed",
+                    name,
+                    nb + 1,
+                    start
+                ))
+            }
+            (Some(true), None) => {
+                if !out.is_empty() {
+                    out.push('\n');
+                }
+                start = Some(nb + 1);
+            }
+            (Some(false), Some(_)) => {
+                if !out.is_empty() && !out.ends_with('\n') {
+                    out.push('\n');
+                }
+                start = None;
+            }
+            (Some(false), None) => {
+                return Err(format!(
+                    "`<!-- {}:end -->` on line {} without a `<!-- {}:start -->`",
+                    name,
+                    nb + 1,
+                    name
+                ))
+            }
+            (None, Some(_)) => out.push_str(line),
+            (None, None) => {}
+        }
+    }
+    match start {
+        Some(start) => Err(format!(
+            "`<!-- {}:start -->` on line {} isn't closed",
+            name, start
+        )),
+        None => Ok(out),
+    }
+}
+
+/// Adds `footer` at the end of `content`, after an empty line so it isn't part of the last
+/// paragraph. If the last code block isn't closed, it's closed first.
+fn add_footer(content: &str, footer: &str) -> String {
+    let mut out = content
+        .trim_end_matches(|c| c == '\n' || c == '\r')
+        .to_owned();
+    if let Some(fence) = scan_fences(&out).last() {
+        if !fence.closed {
+            let marker = out[fence.range.start + fence.indent..fence.marker_end].to_owned();
+            out.push('\n');
+            out.push_str(&marker);
+        }
+    }
+    if !out.is_empty() {
+        out.push_str("\n\n");
+    }
+    out.push_str(footer);
+    out.push('\n');
+    out
+}
+
+/// Turns the rust code blocks inside blockquotes into `text` ones.
+fn untest_quoted_fences(content: &str) -> String {
+    let mut out = content.to_owned();
+    // From the end so the ranges stay valid.
+    for fence in fences::scan_quoted_fences(content).iter().rev() {
+        if is_rust_fence(fence.info) {
+            out.replace_range(fence.info_range.clone(), "text");
+        }
+    }
+    out
+}
+
+/// Options which can be given to the `doctest` macro after the file path.
+#[derive(Default)]
+struct DoctestOptions {
+    tab_width: Option<usize>,
+    skip_containing: Vec<String>,
+    native: bool,
+    attach: bool,
+    encoding: Option<Encoding>,
+    register: bool,
+    quiet_track: bool,
+    prepend: Option<String>,
+    append: Option<String>,
+    from_rust: Option<String>,
+    from_rust_all: Option<String>,
+    lint_prompts: bool,
+    base_dir: Option<String>,
+    files: Vec<String>,
+    join: Vec<String>,
+    into: Option<String>,
+    /// `as-listed` or `sorted`.
+    order: Option<String>,
+    allow_unknown: bool,
+    markers: Option<String>,
+    footer: Option<String>,
+    skip_blockquotes: bool,
+    replace: Vec<(String, String)>,
+    cfg_variants: Vec<(String, String)>,
+    syntax_check: bool,
+    lint_mixed_indent: bool,
+    check_links: bool,
+    /// With `check_relative_links = "warn"`, the broken links are reported with warnings.
+    links_warn: bool,
+    non_empty: bool,
+    source_comment: bool,
+    must_match: Option<String>,
+    separator: Option<String>,
+    unignore: bool,
+    /// The errors for the unknown flags and options, which are only emitted if `allow_unknown`
+    /// isn't used (it can come after them).
+    unknown: Vec<Error>,
+    require_feature: Option<String>,
+    main_returns: Option<String>,
+    expose_clean: Option<String>,
+    expose_numbered: Option<String>,
+    expose_len: Option<String>,
+    build_up: Option<String>,
+    report_skipped: bool,
+    strict: bool,
+    dedup_blocks: bool,
+    block_attrs: Vec<String>,
+    base: Option<String>,
+    locales: Vec<String>,
+    ext: Option<String>,
+    debug_path: bool,
+    strip_front_matter: bool,
+    preserve_lines: bool,
+    holder: Option<Holder>,
+    /// Span of the `holder` value, for the errors.
+    holder_span: Option<Span>,
+    lint_templates: Vec<String>,
+    data: Option<String>,
+    template: Option<String>,
+    add_modifier: Option<String>,
+    to: Option<String>,
+    source_markers: bool,
+    ranges_from: Option<String>,
+    expect_all_fail: bool,
+    via: Option<String>,
+    crate_suffix: bool,
+    auto_name: bool,
+    header: bool,
+    header_strip: usize,
+    max_file_size: Option<u64>,
+    details: Option<String>,
+    edition: Option<String>,
+    expect_crate: Option<String>,
+    split: bool,
+    mdbook_includes: bool,
+    anchor: Option<String>,
+    cfg_doctest: bool,
+    optional: bool,
+    once: bool,
+    no_std: bool,
+    hidden: bool,
+    strip_comments: bool,
+    print_modes: bool,
+    extern_crate: Option<String>,
+    fence_as: Option<String>,
+    hide_lines: Vec<(usize, usize)>,
+    stable_names: bool,
+    forbidden_crates: Vec<String>,
+    on_oversize: Option<String>,
+    /// The file paths and `lit` values, in the order they were given. Only filled if `lit` is
+    /// used.
+    pieces: Vec<Piece>,
+    /// Set by `from_file`: like `attach` but generates the `#[doc]` attribute, after the items
+    /// tracking the file.
+    attribute: bool,
+}
+
+/// A part of the content given to `doctest`.
+enum Piece {
+    Lit(String),
+    File(String),
+}
+
+impl DoctestOptions {
+    /// Returns `false` if `flag` isn't a known flag.
+    fn set_flag(&mut self, flag: &str) -> bool {
+        match flag {
+            "native" => self.native = true,
+            "attach" => self.attach = true,
+            "register" => self.register = true,
+            "quiet_track" => self.quiet_track = true,
+            "lint_prompts" => self.lint_prompts = true,
+            "report_skipped" => self.report_skipped = true,
+            "strict" => self.strict = true,
+            "dedup_blocks" => self.dedup_blocks = true,
+            "debug_path" => self.debug_path = true,
+            "source_markers" => self.source_markers = true,
+            "expect_all_fail" => self.expect_all_fail = true,
+            "crate_suffix" => self.crate_suffix = true,
+            "auto_name" => self.auto_name = true,
+            "split" => self.split = true,
+            "cfg_doctest" => self.cfg_doctest = true,
+            "optional" => self.optional = true,
+            "once" => self.once = true,
+            "no_std" => self.no_std = true,
+            "hidden" => self.hidden = true,
+            "allow_unknown" => self.allow_unknown = true,
+            "skip_blockquotes" => self.skip_blockquotes = true,
+            "syntax_check" => self.syntax_check = true,
+            "lint_mixed_indent" => self.lint_mixed_indent = true,
+            "check_relative_links" => self.check_links = true,
+            "non_empty" => self.non_empty = true,
+            "source_comment" => self.source_comment = true,
+            "unignore" => self.unignore = true,
+            "markers" => self.markers = Some("doctest".to_owned()),
+            "footer" => {
+                self.footer = Some(format!(
+                    "_Examples verified by doc-comment v{}_",
+                    env!("CARGO_PKG_VERSION")
+                ))
+            }
+            "strip_comments" => self.strip_comments = true,
+            "print_modes" => self.print_modes = true,
+            "mdbook_includes" => self.mdbook_includes = true,
+            "stable_names" => self.stable_names = true,
+            "header" => self.header = true,
+            "strip_front_matter" => self.strip_front_matter = true,
+            "preserve_lines" => self.preserve_lines = true,
+            "edition2015" | "edition2018" | "edition2021" => self.edition = Some(flag.to_owned()),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Returns the path of `file`, taking `base_dir` into account.
+    fn path(&self, file: &str) -> PathBuf {
+        match self.base_dir {
+            Some(ref base_dir) => Path::new(base_dir).join(file),
+            None => PathBuf::from(file),
+        }
+    }
+
+    /// Returns the attributes added before the `#[doc]` attribute of the generated items.
+    fn holder_attrs(&self) -> String {
+        let mut attrs = String::new();
+        if self.cfg_doctest {
+            attrs.push_str("#[cfg(doctest)]\n");
+        }
+        if self.hidden {
+            attrs.push_str("#[doc(hidden)]\n");
+        }
+        attrs
+    }
+
+    fn rewrites_content(&self) -> bool {
+        self.tab_width.is_some()
+            || !self.skip_containing.is_empty()
+            || self.encoding.is_some()
+            || self.prepend.is_some()
+            || self.append.is_some()
+            || self.from_rust.is_some()
+            || self.main_returns.is_some()
+            || self.dedup_blocks
+            || !self.block_attrs.is_empty()
+            || self.strip_front_matter
+            || self.data.is_some()
+            || self.add_modifier.is_some()
+            || self.source_markers
+            || self.ranges_from.is_some()
+            || self.header
+            || self.details.is_some()
+            || self.edition.is_some()
+            || self.mdbook_includes
+            || self.anchor.is_some()
+            || self.fence_as.is_some()
+            || self.no_std
+            || self.extern_crate.is_some()
+            || self.markers.is_some()
+            || self.footer.is_some()
+            || self.skip_blockquotes
+            || self.source_comment
+            || self.unignore
+            || !self.replace.is_empty()
+            || !self.pieces.is_empty()
+    }
+
+    fn read_file(&self, path: &Path, span: Span, includes: &mut String) -> Result<String, Error> {
+        self.read_raw_file(path, span, includes)
+            .map(|content| end_with_newline(&content))
+    }
+
+    /// Same as `read_file` but returns the file content as is.
+    fn read_raw_file(
+        &self,
+        path: &Path,
+        span: Span,
+        includes: &mut String,
+    ) -> Result<String, Error> {
+        match self.encoding {
+            Some(encoding) => include_encoded_file(path, encoding, span, includes),
+            None => include_raw_file("include_str", path, span, includes),
+        }
+    }
+
+    /// Reads the file at `path` along with the `prepend` and `append` files if any.
+    fn read_content(
+        &self,
+        path: &Path,
+        span: Span,
+        includes: &mut String,
+    ) -> Result<String, Error> {
+        let mut content = String::new();
+        if let Some(ref prepend) = self.prepend {
+            content.push_str(&self.read_file(Path::new(prepend), span, includes)?);
+            content.push('\n');
+        }
+        let mut main = String::new();
+        if self.pieces.is_empty() {
+            main = self.read_main_file(path, span, includes)?;
+        }
+        for piece in &self.pieces {
+            match *piece {
+                Piece::Lit(ref lit) => main.push_str(lit),
+                Piece::File(ref file) => {
+                    main.push_str(&self.read_main_file(&self.path(file), span, includes)?)
+                }
+            }
+        }
+        if let Some(ref name) = self.markers {
+            main = extract_regions(&main, name).map_err(|e| {
+                Error::new(span, format!("Failed to read `{}`: {}", path.display(), e))
+            })?;
+        }
+        if let Some(ref anchor) = self.anchor {
+            let code = mdbook::extract_anchor(&main, anchor).map_err(|e| {
+                Error::new(span, format!("Failed to read `{}`: {}", path.display(), e))
+            })?;
+            main = wrap_in_fence(&code, "rust");
+        }
+        if let Some(ref info) = self.fence_as {
+            main = wrap_in_fence(&hide_lines(&main, &self.hide_lines), info);
+        }
+        if let Some(ref ranges_from) = self.ranges_from {
+            let ranges = include_file("include_str", Path::new(ranges_from), span, includes)?;
+            let ranges = parse_ranges(&ranges).map_err(|e| {
+                Error::new(span, format!("Failed to parse `{}`: {}", ranges_from, e))
+            })?;
+            main = keep_lines(&main, &ranges);
+        }
+        if self.from_rust.is_some() {
+            main = extract_doc_comments(&main, self.preserve_lines);
+        }
+        if self.strip_front_matter {
+            main = strip_front_matter(&main, self.preserve_lines);
+        }
+        if let Some(ref data) = self.data {
+            main = self.fill_template(&main, Path::new(data), span, includes)?;
+        }
+        content.push_str(&main);
+        if let Some(ref append) = self.append {
+            content.push('\n');
+            content.push_str(&self.read_file(Path::new(append), span, includes)?);
+        }
+        Ok(content)
+    }
+
+    /// Reads the file at `path`, resolving its `{{#include ...}}` directives with
+    /// `mdbook_includes`.
+    fn read_main_file(
+        &self,
+        path: &Path,
+        span: Span,
+        includes: &mut String,
+    ) -> Result<String, Error> {
+        // Only the markdown is put at column zero, the other files are used as written.
+        let content = start_at_column_zero(&self.read_file(path, span, includes)?).to_owned();
+        if !self.mdbook_includes {
+            return Ok(content);
+        }
+        let mut stack = vec![(include_key(path), path.to_path_buf())];
+        resolve_includes(&content, path, span, includes, &mut stack)
+    }
+
+    /// Replaces the `{{key}}` placeholders of `template` with the values of the `data` file.
+    fn fill_template(
+        &self,
+        template: &str,
+        data: &Path,
+        span: Span,
+        includes: &mut String,
+    ) -> Result<String, Error> {
+        let content = include_file("include_str", data, span, includes)?;
+        let values = match data.extension().and_then(|e| e.to_str()) {
+            Some("toml") => template::parse_toml(&content),
+            Some("json") => template::parse_json(&content),
+            _ => {
+                return Err(Error::new(
+                    span,
+                    format!(
+                        "`data` must be a `.toml` or `.json` file, found `{}`",
+                        data.display()
+                    ),
+                ))
+            }
+        }
+        .map_err(|e| Error::new(span, format!("Failed to parse `{}`: {}", data.display(), e)))?;
+        template::fill(template, &values).map_err(|key| {
+            Error::new(
+                span,
+                format!(
+                    "`{{{{{}}}}}` is used in the template but `{}` doesn't have a `{}` value",
+                    key,
+                    data.display(),
+                    key
+                ),
+            )
+        })
+    }
+
+    fn set(&mut self, key: &Ident, value: Vec<TokenTree>) -> Result<(), Error> {
+        match key.to_string().as_str() {
+            "tab_width" => {
+                let width = match value_as_usize(key, &value)? {
+                    0 => {
+                        return Err(Error::new(
+                            value[0].span(),
+                            "`tab_width` cannot be 0".to_owned(),
+                        ))
+                    }
+                    w => w,
+                };
+                self.tab_width = Some(width);
+            }
+            "header_strip" => self.header_strip = value_as_usize(key, &value)?,
+            "max_file_size" => self.max_file_size = Some(value_as_usize(key, &value)? as u64),
+            "order" => {
+                let order = value_as_str(key, &value)?;
+                if order != "as-listed" && order != "sorted" {
+                    return Err(Error::new(
+                        value[0].span(),
+                        format!(
+                            "Unknown `order` value `{}` (expected `as-listed` or `sorted`)",
+                            order
+                        ),
+                    ));
+                }
+                self.order = Some(order);
+            }
+            "on_oversize" => {
+                let action = value_as_str(key, &value)?;
+                if action != "skip" && action != "error" {
+                    return Err(Error::new(
+                        value[0].span(),
+                        format!(
+                            "Unknown `on_oversize` value `{}` (expected `skip` or `error`)",
+                            action
+                        ),
+                    ));
+                }
+                self.on_oversize = Some(action);
+            }
+            "skip_containing" => self.skip_containing.extend(value_as_str_list(key, &value)?),
+            "base" => self.base = Some(value_as_str(key, &value)?),
+            "locales" => self.locales.extend(value_as_str_list(key, &value)?),
+            "ext" => self.ext = Some(value_as_str(key, &value)?),
+            "base_dir" => self.base_dir = Some(value_as_str(key, &value)?),
+            "files" => self.files.extend(value_as_str_list(key, &value)?),
+            "join" => self.join.extend(value_as_str_list(key, &value)?),
+            "into" => self.into = Some(value_as_ident(key, &value)?),
+            "ranges_from" => self.ranges_from = Some(value_as_str(key, &value)?),
+            "expect_crate" => self.expect_crate = Some(value_as_str(key, &value)?),
+            "forbidden_crates" => self
+                .forbidden_crates
+                .extend(value_as_str_list(key, &value)?),
+            "fence_as" => self.fence_as = Some(value_as_str(key, &value)?),
+            "hide_lines" => {
+                let lines = parse_line_list(&value_as_str(key, &value)?)
+                    .map_err(|e| Error::new(value[0].span(), format!("`{}`: {}", key, e)))?;
+                self.hide_lines.extend(lines);
+            }
+            "anchor" => self.anchor = Some(value_as_str(key, &value)?),
+            "markers" => self.markers = Some(value_as_str(key, &value)?),
+            "footer" => self.footer = Some(value_as_str(key, &value)?),
+            "check_relative_links" => match value_as_str(key, &value)?.as_str() {
+                "warn" => {
+                    self.check_links = true;
+                    self.links_warn = true;
+                }
+                _ => {
+                    return Err(Error::new(
+                        value_span(key, &value),
+                        "`check_relative_links` only accepts `\"warn\"`".to_owned(),
+                    ))
+                }
+            },
+            "must_match" => self.must_match = Some(value_as_str(key, &value)?),
+            "separator" => self.separator = Some(value_as_str(key, &value)?),
+            "reexport" => {
+                return Err(Error::new(
+                    key.span(),
+                    "`reexport` isn't supported: the items defined in code blocks only exist in \
+                     the doctests, which are compiled separately from the crate, so they cannot be \
+                     re-exported from the generated module. To use the items of a code block in \
+                     the following ones, use `build_up = NAME`"
+                        .to_owned(),
+                ))
+            }
+            "replace" => self.replace.extend(value_as_str_pairs(key, &value)?),
+            "variants" => self.cfg_variants.extend(value_as_cfg_pairs(key, &value)?),
+            "extern_crate" => self.extern_crate = Some(value_as_str(key, &value)?),
+            "details" => self.details = Some(value_as_str(key, &value)?),
+            "via" => self.via = Some(value_as_path(key, &value)?),
+            "data" => self.data = Some(value_as_str(key, &value)?),
+            "template" => self.template = Some(value_as_str(key, &value)?),
+            "from_rust" => self.from_rust = Some(value_as_str(key, &value)?),
+            "from_rust_all" => self.from_rust_all = Some(value_as_str(key, &value)?),
+            "expose_clean" => self.expose_clean = Some(value_as_ident(key, &value)?),
+            "expose_numbered" => self.expose_numbered = Some(value_as_ident(key, &value)?),
+            "expose_len" => self.expose_len = Some(value_as_ident(key, &value)?),
+            "build_up" => self.build_up = Some(value_as_ident(key, &value)?),
+            "add_modifier" => self.add_modifier = Some(value_as_str(key, &value)?),
+            "to" => self.to = Some(value_as_str(key, &value)?),
+            "block_attrs" => self.block_attrs.extend(
+                value_as_str(key, &value)?
+                    .split(',')
+                    .map(|m| m.trim().to_owned())
+                    .filter(|m| !m.is_empty()),
+            ),
+            "lint_templates" => self.lint_templates.extend(value_as_str_list(key, &value)?),
+            "main_returns" => self.main_returns = Some(value_as_str(key, &value)?),
+            "require_feature" => self.require_feature = Some(value_as_str(key, &value)?),
+            "prepend" => self.prepend = Some(value_as_str(key, &value)?),
+            "append" => self.append = Some(value_as_str(key, &value)?),
+            "holder" => {
+                let name = value_as_str(key, &value)?;
+                match Holder::from_name(&name) {
+                    Some(holder) => {
+                        self.holder = Some(holder);
+                        self.holder_span = Some(value_span(key, &value));
+                    }
+                    None => {
+                        return Err(Error::new(
+                            value[0].span(),
+                            format!(
+                                "Unknown holder `{}` (expected `mod`, `fn`, `struct` or `extern`)",
+                                name
+                            ),
+                        ))
+                    }
+                }
+            }
+            "encoding" => {
+                let name = value_as_str(key, &value)?;
+                match Encoding::from_name(&name) {
+                    Some(encoding) => self.encoding = Some(encoding),
+                    None => {
+                        return Err(Error::new(
+                            value[0].span(),
+                            format!(
+                                "Unsupported encoding `{}` (supported encodings are `latin1` and \
+                                 `windows-1252`)",
+                                name
+                            ),
+                        ))
+                    }
+                }
+            }
+            k => self.unknown.push(Error::new(
+                key.span(),
+                format!("Unknown doctest option `{}`", k),
+            )),
+        }
+        Ok(())
+    }
+
+    /// Returns the paths of the files given to the macro (`file_path` unless `lit` is used).
+    fn main_paths(&self, file_path: &Path) -> Vec<PathBuf> {
+        let mut paths = self
+            .pieces
+            .iter()
+            .filter_map(|piece| match *piece {
+                Piece::File(ref file) => Some(self.path(file)),
+                Piece::Lit(_) => None,
+            })
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            paths.push(file_path.to_path_buf());
+        }
+        paths
+    }
+
+    /// Returns `true` if `optional` is used and one of the files to include doesn't exist.
+    fn skip_missing(&self, file_path: &Path) -> bool {
+        self.optional
+            && self
+                .main_paths(file_path)
+                .iter()
+                .any(|path| !resolve_path(path).exists())
+    }
+
+    /// Returns `true` if one of the files to include is bigger than `max_file_size` and
+    /// `on_oversize = "skip"` is used.
+    fn skip_oversized(&self, file_path: &Path, span: Span) -> Result<bool, Error> {
+        let max = match self.max_file_size {
+            Some(max) => max,
+            None => return Ok(false),
+        };
+        for path in &self.main_paths(file_path) {
+            // If it can't be read, the error will be reported when reading it.
+            let size = match fs::metadata(resolve_path(path)) {
+                Ok(metadata) => metadata.len(),
+                Err(_) => continue,
+            };
+            if size <= max {
+                continue;
+            }
+            if self.on_oversize.as_ref().map(|a| a.as_str()) == Some("skip") {
+                return Ok(true);
+            }
+            return Err(Error::new(
+                span,
+                format!(
+                    "`{}` is {} bytes which is more than `max_file_size` ({} bytes)",
+                    path.display(),
+                    size,
+                    max
+                ),
+            ));
+        }
+        Ok(false)
+    }
+
+    /// Checks the content for the problems the `lint_*` flags ask for.
+    fn lint(&self, content: &str, path: &Path, span: Span) -> Result<(), Error> {
+        if !self.lint_prompts && self.lint_templates.is_empty() {
+            return Ok(());
+        }
+        for fence in scan_fences(content)
+            .iter()
+            .filter(|f| is_rust_fence(f.info))
+        {
+            for (nb, line) in fence.body.lines().enumerate() {
+                if let Some(marker) = self
+                    .lint_templates
+                    .iter()
+                    .find(|m| line.contains(m.as_str()))
+                {
+                    return Err(Error::new(
+                        span,
+                        format!(
+                            "{}:{}: `{}` found in a rust code block, it's probably an unexpanded \
+                             template",
+                            path.display(),
+                            fence.line + nb + 1,
+                            marker,
+                        ),
+                    ));
+                }
+                if !self.lint_prompts {
+                    continue;
+                }
+                let prompt = if line.starts_with("$ ") {
+                    "$ "
+                } else if line.starts_with("> ") {
+                    "> "
+                } else {
+                    continue;
+                };
+                return Err(Error::new(
+                    span,
+                    format!(
+                        "{}:{}: line starts with `{}` which looks like a shell prompt: this code \
+                         block is probably not rust code (you can set its language with \
+                         ```sh for example)",
+                        path.display(),
+                        fence.line + nb + 1,
+                        prompt,
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// With `must_match`, checks that the file at `path` has the same content as the other one.
+    fn check_match(&self, path: &Path, span: Span, includes: &mut String) -> Result<(), Error> {
+        let other = match self.must_match {
+            Some(ref other) => Path::new(other),
+            None => return Ok(()),
+        };
+        // `path` is tracked when it's included. The contents are compared as they are in the files,
+        // before the indentation and the trailing new lines are normalized.
+        let content = self.read_raw_file(path, span, &mut String::new())?;
+        let other_content = self.read_raw_file(other, span, includes)?;
+        match first_difference(&content, &other_content) {
+            Some(line) => Err(Error::new(
+                span,
+                format!(
+                    "`{}` and `{}` differ from line {}",
+                    path.display(),
+                    other.display(),
+                    line
+                ),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// With `syntax_check`, checks that the tested rust code blocks can be tokenized.
+    fn check_syntax(&self, content: &str, path: &Path, span: Span) -> Result<(), Error> {
+        if !self.syntax_check {
+            return Ok(());
+        }
+        for fence in scan_fences(content) {
+            let status = fence.run_status();
+            if status == RunStatus::Ignored
+                || status == RunStatus::NotRust
+                || has_fence_modifier(fence.info, "compile_fail")
+            {
+                continue;
+            }
+            if let Err((line, msg)) = check_tokens(&unhide_lines(fence.body), fence.line + 1) {
+                return Err(Error::new(
+                    span,
+                    format!("{}:{}: {}", path.display(), line, msg),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// With `lint_mixed_indent`, checks that the lines of the rust code blocks aren't indented
+    /// with both tabs and spaces.
+    fn check_indent(&self, content: &str, path: &Path, span: Span) -> Result<(), Error> {
+        if !self.lint_mixed_indent {
+            return Ok(());
+        }
+        for fence in scan_fences(content) {
+            if !is_rust_fence(fence.info) {
+                continue;
+            }
+            if let Some(line) = mixed_indent_line(fence.body) {
+                return Err(Error::new(
+                    span,
+                    format!(
+                        "{}:{}: this line is indented with both tabs and spaces",
+                        path.display(),
+                        fence.line + 1 + line
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// With `check_relative_links`, checks that the relative links of the file point to existing
+    /// files (relative to the directory of the file).
+    fn check_links(
+        &self,
+        content: &str,
+        path: &Path,
+        span: Span,
+        includes: &mut String,
+    ) -> Result<(), Error> {
+        if !self.check_links {
+            return Ok(());
+        }
+        let full_path = resolve_path(path);
+        let dir = full_path.parent().unwrap_or_else(|| Path::new(""));
+        for (line, target) in relative_links(content) {
+            if dir.join(&target).exists() {
+                continue;
+            }
+            let msg = format!(
+                "{}:{}: the link to `{}` is broken, `{}` doesn't exist",
+                path.display(),
+                line,
+                target,
+                dir.join(&target).display()
+            );
+            if !self.links_warn {
+                return Err(Error::new(span, msg));
+            }
+            emit_warning("DoctestBrokenLink", &msg, includes);
+        }
+        Ok(())
+    }
+
+    /// With `non_empty`, checks that `content` isn't empty or only made of whitespace.
+    fn check_non_empty(&self, content: &str, path: &Path, span: Span) -> Result<(), Error> {
+        if self.non_empty && content.trim().is_empty() {
+            return Err(Error::new(
+                span,
+                format!("`{}` is empty but `non_empty` is used", path.display()),
+            ));
+        }
+        Ok(())
+    }
+
+    /// With `expect_all_fail`, checks that all the rust code blocks are `compile_fail`.
+    fn check_all_fail(&self, content: &str, path: &Path, span: Span) -> Result<(), Error> {
+        if !self.expect_all_fail {
+            return Ok(());
+        }
+        let fences = scan_fences(content);
+        let mut rust_fences = fences.iter().filter(|f| is_rust_fence(f.info)).peekable();
+        if rust_fences.peek().is_none() {
+            return Err(Error::new(
+                span,
+                format!(
+                    "`{}` has no rust code block but `expect_all_fail` is used",
+                    path.display()
+                ),
+            ));
+        }
+        for fence in rust_fences {
+            if !has_fence_modifier(fence.info, "compile_fail") {
+                return Err(Error::new(
+                    span,
+                    format!(
+                        "{}:{}: this rust code block isn't `compile_fail` but `expect_all_fail` is \
+                         used",
+                        path.display(),
+                        fence.line,
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reports the code blocks rustdoc won't test: as a warning with `report_skipped` and as an
+    /// error with `strict`.
+    fn report_skipped(
+        &self,
+        content: &str,
+        path: &Path,
+        span: Span,
+        includes: &mut String,
+    ) -> Result<(), Error> {
+        if !self.report_skipped && !self.strict {
+            return Ok(());
+        }
+        let skipped = scan_fences(content)
+            .iter()
+            .filter_map(|f| match f.run_status() {
+                RunStatus::Ignored | RunStatus::NotRust => {
+                    Some(format!("line {} (`{}`)", f.line, f.info))
+                }
+                RunStatus::Run | RunStatus::Compile => None,
+            })
+            .collect::<Vec<_>>();
+        if skipped.is_empty() {
+            return Ok(());
+        }
+        let msg = format!(
+            "rustdoc won't test {} code block{} of `{}`: {}",
+            skipped.len(),
+            if skipped.len() > 1 { "s" } else { "" },
+            path.display(),
+            skipped.join(", "),
+        );
+        if self.strict {
+            return Err(Error::new(span, msg));
+        }
+        emit_warning("DoctestSkipped", &msg, includes);
+        Ok(())
+    }
+
+    /// With `unignore`, emits a warning with the number of code blocks which aren't `ignore`d
+    /// anymore.
+    fn report_unignored(&self, content: &str, path: &Path, includes: &mut String) {
+        if !self.unignore {
+            return;
+        }
+        let count = scan_fences(content)
+            .iter()
+            .filter(|f| is_rust_fence(f.info) && has_fence_modifier(f.info, "ignore"))
+            .count();
+        if count > 0 {
+            emit_warning(
+                "DoctestUnignored",
+                &format!(
+                    "{} `ignore`d code block{} of `{}` will be tested",
+                    count,
+                    if count > 1 { "s" } else { "" },
+                    path.display()
+                ),
+                includes,
+            );
+        }
+    }
+
+    /// Emits a warning (or an error with `strict`) describing what rustdoc does with each code
+    /// block.
+    fn print_modes(
+        &self,
+        content: &str,
+        path: &Path,
+        span: Span,
+        includes: &mut String,
+    ) -> Result<(), Error> {
+        if !self.print_modes {
+            return Ok(());
+        }
+        let msg = format!(
+            "code blocks of `{}`:\n{}",
+            path.display(),
+            fence_modes(content).join("\n")
+        );
+        if self.strict {
+            return Err(Error::new(span, msg));
+        }
+        emit_warning("DoctestModes", &msg, includes);
+        Ok(())
+    }
+
+    /// Emits an error if a rust code block uses one of the `forbidden_crates` and a warning if
+    /// none uses `expect_crate`.
+    fn check_crate_names(
+        &self,
+        content: &str,
+        path: &Path,
+        span: Span,
+        includes: &mut String,
+    ) -> Result<(), Error> {
+        if self.expect_crate.is_none() && self.forbidden_crates.is_empty() {
+            return Ok(());
+        }
+        let mut expected_found = false;
+        for fence in scan_fences(content)
+            .iter()
+            .filter(|f| is_rust_fence(f.info))
+        {
+            for (nb, line) in fence.body.lines().enumerate() {
+                if let Some(name) = self
+                    .forbidden_crates
+                    .iter()
+                    .find(|name| uses_crate(line, name))
+                {
+                    return Err(Error::new(
+                        span,
+                        format!(
+                            "{}:{}: `{}` is used in a rust code block{}",
+                            path.display(),
+                            fence.line + nb + 1,
+                            name,
+                            match self.expect_crate {
+                                Some(ref expected) => format!(" instead of `{}`", expected),
+                                None => String::new(),
+                            },
+                        ),
+                    ));
+                }
+                if let Some(ref expected) = self.expect_crate {
+                    expected_found = expected_found || uses_crate(line, expected);
+                }
+            }
+        }
+        if let Some(ref expected) = self.expect_crate {
+            if !expected_found {
+                let msg = format!(
+                    "no rust code block of `{}` uses `{}`",
+                    path.display(),
+                    expected
+                );
+                emit_warning("DoctestCrateName", &msg, includes);
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&self, mut content: String) -> String {
+        if self.unignore {
+            content = rewrite_fences(&content, |info, _| {
+                if is_rust_fence(info) {
+                    remove_fence_modifier(info, "ignore");
+                }
+            });
+        }
+        for (from, to) in &self.replace {
+            if !from.is_empty() {
+                content = content.replace(from.as_str(), to);
+            }
+        }
+        if let Some(tab_width) = self.tab_width {
+            content = rewrite_fences(&content, |_, body| *body = expand_tabs(body, tab_width));
+        }
+        if !self.skip_containing.is_empty() {
+            content = rewrite_fences(&content, |info, body| {
+                if is_rust_fence(info) && self.skip_containing.iter().any(|s| body.contains(s)) {
+                    *info = "text".to_owned();
+                }
+            });
+        }
+        if self.skip_blockquotes {
+            content = untest_quoted_fences(&content);
+        }
+        if self.dedup_blocks {
+            let mut seen = Vec::new();
+            content = rewrite_fences(&content, |info, body| {
+                if !is_rust_fence(info) {
+                    return;
+                }
+                let normalized = normalize_body(body);
+                if seen.contains(&normalized) {
+                    *info = "text".to_owned();
+                } else {
+                    seen.push(normalized);
+                }
+            });
+        }
+        if let Some(ref edition) = self.edition {
+            content = set_edition(&content, edition);
+        }
+        if !self.block_attrs.is_empty() {
+            content = add_block_attrs(&content, &self.block_attrs);
+        }
+        if let (Some(ref modifier), Some(ref target)) = (&self.add_modifier, &self.to) {
+            content = add_modifier_to(&content, modifier, target);
+        }
+        if let Some(ref ty) = self.main_returns {
+            content = rewrite_fences(&content, |info, body| {
+                if is_rust_fence(info) && !body.contains("fn main") {
+                    *body = wrap_main(body, ty);
+                }
+            });
+        }
+        if let Some(ref krate) = self.extern_crate {
+            content = rewrite_fences(&content, |info, body| {
+                if is_rust_fence(info) {
+                    *body = add_extern_crate(body, krate);
+                }
+            });
+        }
+        if self.no_std {
+            content = rewrite_fences(&content, |info, body| {
+                if is_rust_fence(info) {
+                    *body = add_no_std(body);
+                }
+            });
+        }
+        content
+    }
+}
+
+/// Concatenates the rust code blocks of `content` tested by rustdoc into a single code block for
+/// `build_up`, so they share the same scope. The `compile_fail` and `ignore`d ones are left out
+/// since they would make the whole code block fail (or not run). The code block gets the
+/// modifiers of all of them.
+fn build_up(content: &str) -> String {
+    let mut info = "rust".to_owned();
+    let mut body = String::new();
+    for fence in scan_fences(content) {
+        if fence.run_status() == RunStatus::Ignored
+            || fence.run_status() == RunStatus::NotRust
+            || has_fence_modifier(fence.info, "compile_fail")
+        {
+            continue;
+        }
+        for modifier in fence
+            .info
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|m| !m.is_empty())
+        {
+            add_fence_modifier(&mut info, modifier);
+        }
+        if !body.is_empty() {
+            body.push('\n');
+        }
+        body.push_str(fence.body);
+        if !body.ends_with('\n') {
+            body.push('\n');
+        }
+    }
+    wrap_in_fence(&body, &info)
+}
+
+/// Returns the content exposed by `expose_clean`: the rust code blocks lose their hidden lines
+/// (and their comments with `strip_comments`) and their info string is reduced to `rust`.
+fn clean_content(content: &str, strip_comments: bool) -> String {
+    rewrite_fences(content, |info, body| {
+        if is_rust_fence(info) {
+            // The test modifiers (`no_run`, `edition2018`...) don't mean anything outside of
+            // rustdoc, only the language is kept.
+            if !info.is_empty() {
+                *info = "rust".to_owned();
+            }
+            *body = strip_hidden_lines(body);
+            if strip_comments {
+                *body = self::strip_comments(body);
+            }
+        }
+    })
+}
+
+/// Returns the names of the modules generated by `split` for the code blocks with the given
+/// bodies: `<prefix>_<n>`, or `<prefix>_<hash>` with `stable_names` so adding or moving a code
+/// block doesn't rename the other ones. Identical code blocks get a `_<n>` suffix.
+fn split_names(bodies: &[&str], prefix: &str, stable_names: bool) -> Vec<String> {
+    let mut names: Vec<String> = Vec::with_capacity(bodies.len());
+    for (nb, body) in bodies.iter().enumerate() {
+        if !stable_names {
+            names.push(format!("{}_{}", prefix, nb + 1));
+            continue;
+        }
+        let base = format!("{}_{:06x}", prefix, fnv1a(body) & 0xff_ffff);
+        let mut name = base.clone();
+        let mut index = 2;
+        while names.contains(&name) {
+            name = format!("{}_{}", base, index);
+            index += 1;
+        }
+        names.push(name);
+    }
+    names
+}
+
+/// The 32-bit FNV-1a hash: it's small and stable across rust versions, unlike `DefaultHasher`.
+fn fnv1a(s: &str) -> u32 {
+    s.bytes().fold(0x811c_9dc5, |hash, b| {
+        (hash ^ u32::from(b)).wrapping_mul(0x0100_0193)
+    })
+}
+
+thread_local! {
+    /// The files included with `once` so far. Nothing guarantees how long it lives, see `once`.
+    static INCLUDED_ONCE: std::cell::RefCell<Vec<PathBuf>> = std::cell::RefCell::new(Vec::new());
+}
+
+/// Returns `false` if `path` was already included with `once`.
+fn first_inclusion(path: &Path) -> bool {
+    let key = include_key(path);
+    INCLUDED_ONCE.with(|included| {
+        let mut included = included.borrow_mut();
+        if included.contains(&key) {
+            false
+        } else {
+            included.push(key);
+            true
+        }
+    })
+}
+
+/// Returns what is used to know if a file is included twice by `mdbook_includes`.
+fn include_key(path: &Path) -> PathBuf {
+    let full_path = resolve_path(path);
+    fs::canonicalize(&full_path).unwrap_or(full_path)
+}
+
+/// Replaces the `{{#include ...}}` directives of `content` (the content of `path`) with the files
+/// they point to, relative to `path`, recursively. `stack` contains the files being included.
+fn resolve_includes(
+    content: &str,
+    path: &Path,
+    span: Span,
+    includes: &mut String,
+    stack: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<String, Error> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut out = String::with_capacity(content.len());
+    let mut pos = 0;
+    for directive in mdbook::find_includes(content) {
+        out.push_str(&content[pos..directive.range.start]);
+        pos = directive.range.end;
+        let sub_path = dir.join(directive.path);
+        let key = include_key(&sub_path);
+        if stack.iter().any(|(k, _)| *k == key) {
+            let chain = stack
+                .iter()
+                .map(|(_, p)| format!("`{}`", p.display()))
+                .collect::<Vec<_>>();
+            return Err(Error::new(
+                span,
+                format!(
+                    "`{}` is included recursively: {} -> `{}`",
+                    sub_path.display(),
+                    chain.join(" -> "),
+                    sub_path.display()
+                ),
+            ));
+        }
+        let mut sub_content = include_file("include_str", &sub_path, span, includes)?;
+        if let Some(anchor) = directive.anchor {
+            sub_content = mdbook::extract_anchor(&sub_content, anchor).map_err(|e| {
+                Error::new(
+                    span,
+                    format!("Failed to include `{}`: {}", sub_path.display(), e),
+                )
+            })?;
+        }
+        stack.push((key, sub_path.clone()));
+        out.push_str(&resolve_includes(
+            &sub_content,
+            &sub_path,
+            span,
+            includes,
+            stack,
+        )?);
+        stack.pop();
+    }
+    out.push_str(&content[pos..]);
+    Ok(out)
+}
+
+/// Proc-macros cannot emit warnings on stable, so we use a deprecated item named `name` instead.
+fn emit_warning(name: &str, msg: &str, includes: &mut String) {
+    includes.push_str(&format!(
+        "const _: () = {{ #[deprecated(note = \"{}\")] struct {}; let _ = {}; }};",
+        escape_content(msg),
+        name,
+        name,
+    ));
+}
+
+/// Returns a line for each code block of `content` with its line number, its info string and what
+/// rustdoc does with it.
+fn fence_modes(content: &str) -> Vec<String> {
+    let fences = scan_fences(content);
+    if fences.is_empty() {
+        return vec!["  no code blocks".to_owned()];
+    }
+    fences
+        .iter()
+        .map(|f| {
+            let mode = match f.run_status() {
+                RunStatus::Run if has_fence_modifier(f.info, "should_panic") => "run, must panic",
+                RunStatus::Run => "run",
+                RunStatus::Compile if has_fence_modifier(f.info, "compile_fail") => {
+                    "must fail to compile"
+                }
+                RunStatus::Compile => "compiled only",
+                RunStatus::Ignored => "ignored",
+                RunStatus::NotRust => "not tested (not rust)",
+            };
+            if f.info.is_empty() {
+                format!("  line {}: {}", f.line, mode)
+            } else {
+                format!("  line {} (`{}`): {}", f.line, f.info, mode)
+            }
+        })
+        .collect()
+}
+
+/// Returns `true` if `line` contains a `krate::` path or an `extern crate krate`.
+fn uses_crate(line: &str, krate: &str) -> bool {
+    let is_ident_char = |c: char| c.is_alphanumeric() || c == '_';
+    line.match_indices(krate).any(|(pos, _)| {
+        let before = &line[..pos];
+        let after = &line[pos + krate.len()..];
+        if before.chars().next_back().map(is_ident_char) == Some(true) {
+            return false;
+        }
+        after.starts_with("::")
+            || (before.trim_end().ends_with("extern crate")
+                && after.chars().next().map(is_ident_char) != Some(true))
+    })
+}
+
+/// Takes all the tokens until the next `,` (or the end).
+fn take_value(parts: &mut Peekable<ProcIter>) -> Vec<TokenTree> {
+    let mut value = Vec::new();
+    loop {
+        match parts.peek() {
+            None => break,
+            Some(TokenTree::Punct(p)) if p.as_char() == ',' => break,
+            _ => value.push(parts.next().unwrap()),
+        }
+    }
+    value
+}
+
+/// Returns the span of the value, or of its key if the value is empty.
+fn value_span(key: &Ident, value: &[TokenTree]) -> Span {
+    value
+        .first()
+        .map(|t| t.span())
+        .unwrap_or_else(|| key.span())
+}
+
+fn value_as_usize(key: &Ident, value: &[TokenTree]) -> Result<usize, Error> {
+    match value {
+        [TokenTree::Literal(l)] => l.to_string().parse().map_err(|_| {
+            Error::new(
+                l.span(),
+                format!("`{}` expects an integer, found `{}`", key, l),
+            )
+        }),
+        _ => Err(Error::new(
+            value_span(key, value),
+            format!("`{}` expects an integer", key),
+        )),
+    }
+}
+
+/// Returns the value of a string literal (raw or not), with its escapes processed. Returns
+/// `Ok(None)` if `tree` isn't a string literal.
+fn literal_value(tree: &TokenTree) -> Result<Option<String>, Error> {
+    match tree {
+        TokenTree::Literal(l) => string_value(&l.to_string())
+            .map_err(|()| Error::new(tree.span(), "Invalid string literal".to_owned())),
+        _ => Ok(None),
+    }
+}
+
+/// Same as `literal_value`, from the source text of the literal.
+fn string_value(s: &str) -> Result<Option<String>, ()> {
+    if s.starts_with('r') {
+        let hashes = s[1..].len() - s[1..].trim_start_matches('#').len();
+        let quoted = &s[1 + hashes..s.len() - hashes];
+        if quoted.len() < 2 || !quoted.starts_with('"') || !quoted.ends_with('"') {
+            return Ok(None);
+        }
+        return Ok(Some(quoted[1..quoted.len() - 1].to_owned()));
+    }
+    if !s.starts_with('"') {
+        return Ok(None);
+    }
+    unescape(&s[1..s.len() - 1]).map(Some).ok_or(())
+}
+
+/// Returns the text a literal adds to a `#[doc_comment(...)]` documentation: the value of a
+/// (byte) string or (byte) character, the source text of a number.
+fn doc_literal_value(l: &Literal) -> Result<String, Error> {
+    let print = l.to_string();
+    let unprefixed =
+        if print.starts_with("b\'") || print.starts_with("b\"") || print.starts_with("br") {
+            &print[1..]
+        } else {
+            &print[..]
+        };
+    let value = if unprefixed.starts_with('\'') {
+        let inner = &unprefixed[1..unprefixed.len() - 1];
+        // `"` doesn't need to be escaped in a character, unlike in a string.
+        if inner == "\"" {
+            Ok(Some(inner.to_owned()))
+        } else {
+            unescape(inner).map(Some).ok_or(())
+        }
+    } else {
+        string_value(unprefixed)
+    };
+    match value {
+        Ok(Some(value)) => Ok(value),
+        Ok(None) => Ok(print),
+        Err(()) => Err(Error::new(l.span(), "Invalid string literal".to_owned())),
+    }
+}
+
+fn value_as_str(key: &Ident, value: &[TokenTree]) -> Result<String, Error> {
+    match value {
+        [t] => literal_value(t)?.ok_or_else(|| {
+            Error::new(
+                t.span(),
+                format!("`{}` expects a string literal, found `{}`", key, t),
+            )
+        }),
+        _ => Err(Error::new(
+            value_span(key, value),
+            format!("`{}` expects a string literal", key),
+        )),
+    }
+}
+
+fn value_as_ident(key: &Ident, value: &[TokenTree]) -> Result<String, Error> {
+    match value {
+        [TokenTree::Ident(i)] => Ok(i.to_string()),
+        _ => Err(Error::new(
+            value_span(key, value),
+            format!("`{}` expects an identifier", key),
+        )),
+    }
+}
+
+/// Accepts a path like `a::b` (to a macro for example).
+fn value_as_path(key: &Ident, value: &[TokenTree]) -> Result<String, Error> {
+    let is_path = !value.is_empty()
+        && value.iter().all(|t| match t {
+            TokenTree::Ident(_) => true,
+            TokenTree::Punct(p) => p.as_char() == ':',
+            _ => false,
+        });
+    if !is_path {
+        return Err(Error::new(
+            value_span(key, value),
+            format!("`{}` expects a path", key),
+        ));
+    }
+    Ok(TokenStream::from_iter(value.iter().cloned()).to_string())
+}
+
+/// Accepts either a string literal or a list of string literals (`["a", "b"]`).
+fn value_as_str_list(key: &Ident, value: &[TokenTree]) -> Result<Vec<String>, Error> {
+    match value {
+        [TokenTree::Group(g)] if g.delimiter() == Delimiter::Bracket => g
+            .stream()
+            .into_iter()
+            .filter(|t| match t {
+                TokenTree::Punct(p) => p.as_char() != ',',
+                _ => true,
+            })
+            .map(|t| {
+                literal_value(&t)?.ok_or_else(|| {
+                    Error::new(
+                        t.span(),
+                        format!("`{}` expects a list of string literals, found `{}`", key, t),
+                    )
+                })
+            })
+            .collect(),
+        _ => Ok(vec![value_as_str(key, value)?]),
+    }
+}
+
+/// Parses a `[("from", "to"), ...]` list. The strings are unescaped.
+fn value_as_str_pairs(key: &Ident, value: &[TokenTree]) -> Result<Vec<(String, String)>, Error> {
+    let expected = |span: Span| {
+        Error::new(
+            span,
+            format!(
+                "`{}` expects a list of `(\"from\", \"to\")` string literal pairs",
+                key
+            ),
+        )
+    };
+    let group = match value {
+        [TokenTree::Group(g)] if g.delimiter() == Delimiter::Bracket => g,
+        _ => return Err(expected(value_span(key, value))),
+    };
+    let mut pairs = Vec::new();
+    for t in group.stream() {
+        let pair = match t {
+            TokenTree::Punct(ref p) if p.as_char() == ',' => continue,
+            TokenTree::Group(ref g) if g.delimiter() == Delimiter::Parenthesis => g,
+            _ => return Err(expected(t.span())),
+        };
+        let strings = pair
+            .stream()
+            .into_iter()
+            .filter(|t| match t {
+                TokenTree::Punct(p) => p.as_char() != ',',
+                _ => true,
+            })
+            .map(|t| literal_value(&t))
+            .collect::<Result<Option<Vec<_>>, Error>>()?;
+        match strings {
+            Some(ref strings) if strings.len() == 2 => {
+                pairs.push((strings[0].clone(), strings[1].clone()))
+            }
+            _ => return Err(expected(pair.span())),
+        }
+    }
+    Ok(pairs)
+}
+
+/// Parses a `[(predicate, name), ...]` list, `predicate` being anything `#[cfg]` accepts.
+fn value_as_cfg_pairs(key: &Ident, value: &[TokenTree]) -> Result<Vec<(String, String)>, Error> {
+    let expected = |span: Span| {
+        Error::new(
+            span,
+            format!(
+                "`{}` expects a list of `(predicate, name)` pairs, like `[(unix, readme_unix)]`",
+                key
+            ),
+        )
+    };
+    let group = match value {
+        [TokenTree::Group(g)] if g.delimiter() == Delimiter::Bracket => g,
+        _ => return Err(expected(value_span(key, value))),
+    };
+    let mut pairs = Vec::new();
+    for t in group.stream() {
+        let pair = match t {
+            TokenTree::Punct(ref p) if p.as_char() == ',' => continue,
+            TokenTree::Group(ref g) if g.delimiter() == Delimiter::Parenthesis => g,
+            _ => return Err(expected(t.span())),
+        };
+        let mut tokens = pair.stream().into_iter().collect::<Vec<_>>();
+        match tokens.pop() {
+            Some(TokenTree::Ident(ref name)) if !is_keyword(&name.to_string()) => {
+                match tokens.pop() {
+                    Some(TokenTree::Punct(ref p)) if p.as_char() == ',' && !tokens.is_empty() => {
+                        let predicate = TokenStream::from_iter(tokens).to_string();
+                        pairs.push((predicate, name.to_string()));
+                    }
+                    _ => return Err(expected(pair.span())),
+                }
+            }
+            _ => return Err(expected(pair.span())),
+        }
+    }
+    Ok(pairs)
+}
+
+fn parse_macro_call(
+    ident: &Ident,
+    attrs: &mut Peekable<ProcIter>,
+    out: &mut String,
+    includes: &mut String,
+) -> Result<(), Error> {
+    if ident.to_string() != "include_str" {
+        return Err(Error::new(
+            ident.span(),
+            format!(
+                "Unsupported macro call `{}` in proc_macro (only `include_str` is currently \
+                 supported)",
+                ident
+            ),
+        ));
+    }
+    // First we remove the "!" token
+    attrs.next();
+    match attrs.next() {
+        Some(TokenTree::Group(g)) => {
+            for token in g.stream().into_iter() {
+                match token {
+                    TokenTree::Literal(l) => {
+                        let l_s = match literal_value(&TokenTree::Literal(l.clone()))? {
+                            Some(l_s) => l_s,
+                            None => {
+                                return Err(Error::new(
+                                    l.span(),
+                                    format!("`{}` should be a string literal!", l),
+                                ))
+                            }
+                        };
+                        let path = Path::new(&l_s);
+                        let content = include_file(&ident.to_string(), path, l.span(), includes)?;
+                        out.push_str(&content);
+                    }
+                    TokenTree::Punct(ref p) if p.to_string() == "," => {}
+                    x => {
+                        return Err(Error::new(
+                            x.span(),
+                            format!("Unexpected item `{}` in macro call `{}`", x, ident),
+                        ))
+                    }
+                }
+            }
+            Ok(())
+        }
+        Some(x) => Err(Error::new(
+            x.span(),
+            format!("Unexpected `{}` in macro `{}`", x, ident),
+        )),
+        None => Err(Error::new(
+            ident.span(),
+            format!("Expected item in macro `{}`, found nothing...", ident),
+        )),
+    }
+}
+
+/// A parsed `#[doc_comment(...)]` attribute.
+struct DocAttr {
+    /// The generated attributes.
+    attrs: String,
+    /// With `after = "..."`, the name of the attribute they must be put after.
+    after: Option<String>,
+    /// With `position = "after"`, they're put after the other attributes of the item (and so
+    /// after its `///` comments).
+    at_end: bool,
+}
+
+impl DocAttr {
+    /// Returns `true` if the attributes aren't put before the other ones.
+    fn is_moved(&self) -> bool {
+        self.after.is_some() || self.at_end
+    }
+}
+
+fn parse_attr(attrs: TokenStream, includes: &mut String, is_inner: bool) -> Result<DocAttr, Error> {
+    let mut out = String::new();
+    let mut doc_cfgs = String::new();
+    let mut dedented = false;
+    let mut after = None;
+    let mut at_end = false;
+    let mut attrs = attrs.into_iter().peekable();
+    loop {
+        let attr = match attrs.next() {
+            Some(a) => a,
+            None => break,
+        };
+        match attr {
+            TokenTree::Literal(l) => out.push_str(&doc_literal_value(&l)?),
+            TokenTree::Punct(ref p) if p.to_string() == "," => {}
+            TokenTree::Ident(i) => {
+                if attrs.peek().map(|a| a.to_string() == "!") == Some(true) {
+                    parse_macro_call(&i, &mut attrs, &mut out, includes)?;
+                } else if i.to_string() == "after"
+                    && attrs.peek().map(|a| a.to_string() == "=") == Some(true)
+                {
+                    attrs.next();
+                    match attrs.next().and_then(|t| literal_value(&t).ok()?) {
+                        Some(name) => after = Some(name),
+                        None => {
+                            return Err(Error::new(
+                                i.span(),
+                                "`after` expects an attribute name: `after = \"derive\"`"
+                                    .to_owned(),
+                            ))
+                        }
+                    }
+                } else if i.to_string() == "position"
+                    && attrs.peek().map(|a| a.to_string() == "=") == Some(true)
+                {
+                    attrs.next();
+                    match attrs.next().and_then(|t| literal_value(&t).ok()?) {
+                        Some(ref position) if position == "before" => at_end = false,
+                        Some(ref position) if position == "after" => at_end = true,
+                        _ => {
+                            return Err(Error::new(
+                                i.span(),
+                                "`position` expects `\"before\"` or `\"after\"`".to_owned(),
+                            ))
+                        }
+                    }
+                } else if i.to_string() == "dedent" {
+                    dedented = true;
+                } else if i.to_string() == "doc_cfg" {
+                    match attrs.next() {
+                        Some(TokenTree::Group(ref g))
+                            if g.delimiter() == Delimiter::Parenthesis =>
+                        {
+                            doc_cfgs.push_str(&doc_cfg_attr(&g.stream().to_string()))
+                        }
+                        _ => {
+                            return Err(Error::new(
+                                i.span(),
+                                "`doc_cfg` expects a predicate: `doc_cfg(feature = \"...\")`"
+                                    .to_owned(),
+                            ))
+                        }
+                    }
+                } else {
+                    out.push_str(&i.to_string());
+                }
+            }
+            TokenTree::Group(ref g) if g.delimiter() == Delimiter::None => {
+                // In case we give an ident through a macro, we need to read it from here. However,
+                // if it contains more than just one ident, then it's something else and we need to
+                // panic.
+                let mut it = g.stream().into_iter();
+                match (it.next(), it.next()) {
+                    (Some(TokenTree::Ident(i)), None) => out.push_str(&i.to_string()),
+                    _ => {
+                        return Err(Error::new(
+                            g.span(),
+                            format!("This token isn't supported: `{}`", g),
+                        ))
+                    }
+                }
+            }
+            x => {
+                return Err(Error::new(
+                    x.span(),
+                    format!("This token isn't supported: `{}`", x),
+                ));
+            }
+        }
+    }
+    if after.is_some() && at_end {
+        return Err(Error::call_site(
+            "`after` and `position` cannot be used together".to_owned(),
+        ));
+    }
+    if dedented {
+        out = dedent(&out);
+    }
+    let mut attr = vec![TokenTree::Punct(Punct::new('#', Spacing::Alone))];
+    if is_inner {
+        attr.push(TokenTree::Punct(Punct::new('!', Spacing::Alone)));
+    }
+    let doc = TokenStream::from_iter(vec![
+        TokenTree::Ident(Ident::new("doc", Span::call_site())),
+        TokenTree::Punct(Punct::new('=', Spacing::Alone)),
+        TokenTree::Literal(Literal::string(&out)),
+    ]);
+    attr.push(TokenTree::Group(Group::new(Delimiter::Bracket, doc)));
+    Ok(DocAttr {
+        attrs: TokenStream::from_iter(attr).to_string() + &doc_cfgs,
+        after,
+        at_end,
+    })
+}
+
+/// `#[doc(cfg(...))]` is unstable, so it's only used when building the documentation on docs.rs
+/// (which sets `docsrs`), like it's usually written by hand.
+fn doc_cfg_attr(predicate: &str) -> String {
+    format!("#[cfg_attr(docsrs, doc(cfg({})))]", predicate)
+}
+
+/// Returns `true` if the next token is an ident or a literal, which would be merged with a
+/// previous ident or literal if no space is added between them (`1 as u8` shouldn't become
+/// `1as u8`).
+fn next_is_ident_or_literal(parts: &mut Peekable<ProcIter>) -> bool {
+    match parts.peek() {
+        Some(TokenTree::Ident(_)) | Some(TokenTree::Literal(_)) => true,
+        _ => false,
+    }
+}
+
+/// Returns `true` if the next token is a punctuation which cannot be glued to the previous one.
+/// `'` always can since it starts a lifetime (`<'a`, `&'a`).
+fn next_is_punct(parts: &mut Peekable<ProcIter>) -> bool {
+    match parts.peek() {
+        Some(TokenTree::Punct(p)) => p.as_char() != '\'',
+        _ => false,
+    }
+}
+
+/// Turns the item back into a string, expanding its `#[doc_comment]` attributes. `pending` is an
+/// attribute to put after the one it names.
+fn parse_item(
+    mut parts: Peekable<ProcIter>,
+    includes: &mut String,
+    mut pending: Option<DocAttr>,
+) -> Result<String, Error> {
+    let mut out = String::new();
+    loop {
+        let attr = match parts.next() {
+            Some(a) => a,
+            None => break,
+        };
+        // With `position = "after"`, the attributes go before the first token which isn't part
+        // of an attribute.
+        let in_attr = match attr {
+            TokenTree::Punct(ref p) => p.as_char() == '#',
+            TokenTree::Group(ref g) => g.delimiter() == Delimiter::Bracket && out.ends_with('#'),
+            _ => false,
+        };
+        if !in_attr && pending.as_ref().map(|p| p.at_end) == Some(true) {
+            if let Some(attr) = pending.take() {
+                out.push_str(&attr.attrs);
+            }
+        }
+        match attr {
+            TokenTree::Group(g) => {
+                out.push_str(match g.delimiter() {
+                    Delimiter::Parenthesis => "(",
+                    Delimiter::Brace => "{",
+                    Delimiter::Bracket => "[",
+                    Delimiter::None => "",
+                });
+                out.push_str(&parse_item(
+                    g.stream().into_iter().peekable(),
+                    includes,
+                    None,
+                )?);
+                out.push_str(match g.delimiter() {
+                    Delimiter::Parenthesis => ")",
+                    Delimiter::Brace => "}",
+                    Delimiter::Bracket => "]",
+                    Delimiter::None => "",
+                });
+            }
+            TokenTree::Punct(ref x) if x.to_string() == "#" => match parts.peek() {
+                Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Bracket => {
+                    let stream = g.stream();
+                    let mut sub_parts = stream.into_iter();
+                    let name = match sub_parts.next() {
+                        Some(TokenTree::Ident(ref i)) => i.to_string(),
+                        _ => String::new(),
+                    };
+                    if name != "doc_comment" {
+                        out.push_str(&x.to_string());
+                        if pending.as_ref().and_then(|p| p.after.as_ref()) == Some(&name) {
+                            if let Some(TokenTree::Group(g)) = parts.next() {
+                                out.push('[');
+                                out.push_str(&parse_item(
+                                    g.stream().into_iter().peekable(),
+                                    includes,
+                                    None,
+                                )?);
+                                out.push(']');
+                            }
+                            if let Some(attr) = pending.take() {
+                                out.push_str(&attr.attrs);
+                            }
+                        }
+                        continue;
+                    }
+                    match sub_parts.next() {
+                        Some(TokenTree::Group(g)) => {
+                            let attr = parse_attr(g.stream(), includes, false)?;
+                            parts.next();
+                            if !attr.is_moved() {
+                                out.push_str(&attr.attrs);
+                            } else if pending.is_some() {
+                                return Err(Error::call_site(
+                                    "`after` and `position` can only be used once per item"
+                                        .to_owned(),
+                                ));
+                            } else {
+                                pending = Some(attr);
+                            }
+                        }
+                        _ => {
+                            out.push_str(&x.to_string());
+                        }
+                    }
+                }
+                _ => {
+                    out.push_str(&x.to_string());
+                }
+            },
+            TokenTree::Ident(i) => {
+                out.push_str(&i.to_string());
+                if next_is_ident_or_literal(&mut parts) {
+                    out.push(' ');
+                }
+            }
+            TokenTree::Literal(l) => {
+                out.push_str(&l.to_string());
+                if next_is_ident_or_literal(&mut parts) {
+                    out.push(' ');
+                }
+            }
+            TokenTree::Punct(p) => {
+                out.push_str(&p.to_string());
+                // Punctuations which weren't joined must stay apart: `0 < -1` isn't `0 <- 1`.
+                if p.spacing() == Spacing::Alone && next_is_punct(&mut parts) {
+                    out.push(' ');
+                }
+            }
+        }
+    }
+    if let Some(DocAttr {
+        after: Some(name), ..
+    }) = pending
+    {
+        return Err(Error::call_site(format!(
+            "`after = \"{}\"` is used but the item has no `#[{}]` attribute",
+            name, name
+        )));
+    }
+    Ok(out)
+}
+
+// fn check_if_is_inner(item: &mut Peekable<ProcIter>) -> bool {
+//     match item.peek() {
+//         Some(TokenTree::Ident(i)) => {
+//             i.to_string().is_empty() || format!("{:?}", i.span()) == "#0 bytes(0..0)"
+//         }
 //         _ => false,
 //     }
 // }
 
 /// ```edition2018,no_run
-/// use doc_comment::doc_comment as dc;
+/// use doc_comment::doc_comment as dc;
+///
+/// #[dc("the foo function!")]
+/// pub fn foo() {}
+///
+/// #[dc("enum ", "time!")]
+/// #[derive(Debug)]
+/// pub enum Foo {
+///     #[doc_comment("variant ", 1)]
+///     A,
+///     #[doc_comment("variant ", 2, hello)]
+///     B,
+/// }
+/// ```
+///
+/// The documentation can also come from a file with `include_str!`, on any kind of item (the
+/// code blocks of the file are tested once per item):
+///
+/// ```edition2018,ignore
+/// #[doc_comment::doc_comment(include_str!("../README.md"))]
+/// pub mod guide {}
+/// ```
+///
+/// The generated `#[doc]` attribute is always put before the other attributes of the item. With
+/// `after = "name"`, it's put right after the first `#[name]` attribute instead:
+///
+/// ```edition2018,no_run
+/// #[doc_comment::doc_comment("A point.", after = "derive")]
+/// #[derive(Debug)]
+/// pub struct Point(u8, u8);
+/// ```
+///
+/// With `position = "after"`, it's put after all the other attributes, so the content comes after
+/// the `///` comments of the item (`position = "before"` is the default):
+///
+/// ```edition2018,no_run
+/// /// Hand-written summary.
+/// #[doc_comment::doc_comment(include_str!("../tests/fixtures/body.md"), position = "after")]
+/// pub struct Summary;
+/// ```
+///
+/// `dedent` removes the indentation common to the lines of the documentation (the first line
+/// excepted). The string can then be indented like the code around it, which would otherwise
+/// turn the indented lines into a code block (and so into a doctest):
+///
+/// ```edition2018,no_run
+/// macro_rules! gen_types {
+///     ($tyname:ident) => {
+///         #[doc_comment::doc_comment(
+///             "This is a wonderful generated struct!
+///
+///             ```
+///             let x = ", $tyname, ";
+///             ```",
+///             dedent,
+///         )]
+///         pub struct $tyname;
+///     };
+/// }
+///
+/// gen_types!(FirstOne);
+/// ```
+///
+/// `doc_cfg(...)` adds `#[cfg_attr(docsrs, doc(cfg(...)))]` to the item so rustdoc shows it
+/// requires a feature. Since `doc(cfg)` is unstable, it's only used when `docsrs` is set (which
+/// docs.rs does) and your crate needs `#![cfg_attr(docsrs, feature(doc_cfg))]`:
+///
+/// ```edition2018,no_run
+/// #[doc_comment::doc_comment("Only available with the `serde` feature.", doc_cfg(feature = "serde"))]
+/// pub fn serialize() {}
+/// ```
+///
+/// Unfortunately, due to current rust limitations, you can't use it as an inner attribute yet (you
+/// can check the issue [here](https://github.com/rust-lang/rust/issues/41430)):
+///
+/// ```ignore
+/// pub mod bar {
+///     #![dc("yolo")] // not working!
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn doc_comment(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    // Nothing to add, so we don't generate an empty `#[doc]` which would be rendered as a blank
+    // line.
+    if attrs.is_empty() {
+        return item;
+    }
+    let mut includes = String::new();
+    // let is_inner = check_if_is_inner(&mut item);
+    let is_inner = false;
+    #[cfg(not(feature = "debug"))]
+    {
+        let original = item.clone();
+        // We keep the item so the error doesn't cascade into "unresolved item" ones.
+        let fail = |e: Error| {
+            TokenStream::from_iter(e.into_compile_error().into_iter().chain(original.clone()))
+        };
+        let mut item = item.into_iter().peekable();
+        let (attr, pending) = match parse_attr(attrs, &mut includes, is_inner) {
+            Err(e) => return fail(e),
+            // The attributes are put before the other ones, unless `after` or `position` is
+            // used.
+            Ok(attr) if attr.is_moved() => (String::new(), Some(attr)),
+            Ok(attr) => (attr.attrs, None),
+        };
+        let attr = match parse_generated(&attr) {
+            Err(e) => return fail(e),
+            Ok(attr) => attr.into_iter(),
+        };
+        if !is_inner {
+            let item = match parse_item(item, &mut includes, pending)
+                .and_then(|item| parse_generated(&item))
+            {
+                Err(e) => return fail(e),
+                Ok(item) => item.into_iter(),
+            };
+            let includes = match parse_generated(&includes) {
+                Err(e) => return fail(e),
+                Ok(includes) => includes.into_iter(),
+            };
+            TokenStream::from_iter(attr.chain(item).chain(includes))
+        } else {
+            // This whole thing is just a non-working hack because when you're writing an inner
+            // attribute with this proc-macro, it strangely returns an anonymous module wrapping
+            // everything inside the current scope. But if you try to insert or modify anything, it
+            // doesn't work so for now, it's completely useless...
+            let includes = match parse_generated(&includes) {
+                Err(e) => return fail(e),
+                Ok(includes) => includes,
+            };
+            loop {
+                match item.next() {
+                    Some(TokenTree::Group(g)) => {
+                        let tokens: ProcIter = g.stream().into_iter();
+                        return TokenStream::from_iter(attr.chain(tokens).chain(includes));
+                    }
+                    Some(_) => {}
+                    None => {
+                        // Weird case... It would meant that we can't find a "TokenTree::Group" where
+                        // the inner attribute would be located...
+                        return TokenStream::from_iter(attr.chain(includes));
+                    }
+                }
+            }
+        }
+    }
+    #[cfg(feature = "debug")]
+    {
+        let attr =
+            parse_attr(attrs, &mut includes, is_inner).unwrap_or_else(|e| panic!("{}", e.msg));
+        let (attr, pending) = if attr.is_moved() {
+            (String::new(), Some(attr))
+        } else {
+            (attr.attrs, None)
+        };
+        let it = parse_item(item.into_iter().peekable(), &mut includes, pending)
+            .unwrap_or_else(|e| panic!("{}", e.msg));
+        panic!("{}\n{}\n{}", attr, it, includes);
+    }
+}
+
+/// This proc macro provides a simpler way to test an outer markdown file.
+///
+/// # Example
+///
+/// ```edition2018,no_run
+/// doc_comment::doctest! { "../README.md" }
+/// // It is the equivalent of:
+/// #[doc_comment::doc_comment(include_str!("../README.md"))]
+/// extern {}
 ///
-/// #[dc("the foo function!")]
-/// pub fn foo() {}
+/// // If you want to have a name for your tests:
+/// doc_comment::doctest! { "../README.md", another }
+/// # fn main() {}
+/// ```
 ///
-/// #[dc("enum ", "time!")]
-/// #[derive(Debug)]
-/// pub enum Foo {
-///     #[doc_comment("variant ", 1)]
-///     A,
-///     #[doc_comment("variant ", 2, hello)]
-///     B,
+/// File paths are always used verbatim: characters like `*` or `[` are never interpreted as glob
+/// patterns. Relative paths are resolved from the first of these directories where the file
+/// exists:
+///
+///  1. the directory of the file calling the macro, like `include_str!` does (requires rust 1.88
+///     or newer);
+///  2. the directory of your crate (`CARGO_MANIFEST_DIR`);
+///  3. the `src` directory of this crate, which is only the same directory if the macro is called
+///     from a file at the same depth (like `src/lib.rs`).
+///
+/// If the file exists in none of them, the error lists all the paths which were tried. A leading
+/// `~/` is replaced with the home directory (from `HOME`, or `USERPROFILE` on Windows).
+///
+/// # Options
+///
+/// Options can be given after the file path with the `key = value` syntax. The strings (file
+/// paths included) are read like rust string literals: `"C:\\docs"` is `C:\docs`, and raw
+/// strings (`r"C:\docs"`) can be used as well.
+///
+///  * `tab_width = N`: replaces the tabs used inside code blocks with spaces (up to the next
+///    multiple of `N` columns). Tabs outside of code blocks are left untouched.
+///
+///  * `skip_containing = "..."`: Rust code blocks containing the given string are turned into
+///    `text` blocks so they are not tested anymore. A list of strings (`["a", "b"]`) can be given
+///    as well.
+///  * `replace = [("...", "..."), ...]`: replaces the first string of each pair with the second
+///    one in the file content, in the given order. It's a lightweight templating which doesn't
+///    require the file to use a specific syntax.
+///  * `unignore`: removes the `ignore` modifier of the rust code blocks so they're tested, with
+///    a (deprecation) warning giving how many code blocks it changed.
+///  * `skip_blockquotes`: the rust code blocks inside blockquotes (with lines starting with `> `)
+///    are turned into `text` blocks as well, for the quoted examples which are only illustrative.
+///
+///  * `encoding = "..."`: converts the file to UTF-8 from the given encoding. `latin1` (or
+///    `iso-8859-1`) and `windows-1252` are supported.
+///  * `from_rust = "..."`: tests the markdown written in the `//!` and `///` comments of the
+///    given rust source file instead of a markdown file. In this case, no file path is expected.
+///  * `from_rust_all = "..."`: same as `from_rust`, but each documented item of the file is
+///    tested in its own module, named `<test name>_<item name>` (the file name is used without a
+///    test name) and `<test name>_crate` for the `//!` comments. It allows to test the examples of
+///    a source file without running rustdoc on the whole crate. Options modifying the content
+///    cannot be used with it.
+///  * `data = "..."` and `template = "..."`: tests the markdown generated from the `template`
+///    file, whose `{{key}}` placeholders are replaced with the top-level values of the `data`
+///    file (a `.toml` or `.json` file). No file path is expected in this case. Only strings,
+///    numbers and booleans are supported.
+///  * `files = ["...", ...]`: tests multiple files at once. No file path is expected in this
+///    case. Each file gets its own module, named after the file name (without its extension). If
+///    a test name is given, it's used as a prefix for the module names.
+///  * `join = ["...", ...]` and `into = NAME`: tests multiple files as a single document, in
+///    `mod NAME {}`. The files are concatenated in the given order, separated with two line
+///    returns so a code block or a heading at the end of a file isn't merged with the beginning
+///    of the next one. Unlike with `files`, it's a single test, so it's for a page split across
+///    multiple files.
+///  * `separator = "..."`: what is inserted between the `join` files instead of `"\n\n"`.
+///  * `order = "..."`: the order in which the `files` modules are generated and the `join` files
+///    are concatenated. It's `as-listed` by default, `sorted` sorts the paths lexicographically.
+///  * `lint_templates = "..."`: emits an error if a rust code block contains the given string
+///    (like `${`), which usually means a template wasn't expanded. A list of strings can be given
+///    as well.
+///  * `via = path::to::macro`: generates `#[doc = path::to::macro!("<content>")]` so the content
+///    goes through your own macro before being tested. The macro must accept a string literal and
+///    expand to a string literal (with `concat!` for example). It requires a rust version
+///    supporting macro calls in `#[doc]` attributes (1.54 or newer).
+///  * `holder = "..."`: the item the documentation is put on: `mod` (the default with a test
+///    name), `fn`, `struct` or `extern` (the default without a test name). Only `extern` can be
+///    used without a test name.
+///  * `base = "..."`, `locales = [...]` and `ext = "..."`: tests translated files. It's the same
+///    as `files` with `<base>.<locale>.<ext>` (or `<base>.<ext>` for the `""` locale) for each
+///    locale. `ext` is `md` by default.
+///  * `base_dir = "..."`: directory the file path(s) are relative to.
+///  * `max_file_size = N`: emits an error if a file is bigger than `N` bytes, naming the file. With
+///    `on_oversize = "skip"`, nothing is generated for this file instead (`on_oversize = "error"`
+///    is the default). It prevents including a huge generated file by mistake with `files` or
+///    `base`.
+///  * `prepend = "..."` and `append = "..."`: paths of files to be added before and after the
+///    file content (separated by a new line). It's useful to share a header or a footer between
+///    multiple files.
+///  * `require_feature = "..."`: only generates the tests if the given cargo feature is enabled.
+///    Proc-macros cannot see `cfg`s, so the `CARGO_FEATURE_<name>` environment variable is used
+///    instead (`<name>` being the feature name in uppercase with `-` replaced with `_`). Cargo only
+///    sets these variables for build scripts, so yours needs to forward them to the compiler with
+///    `println!("cargo:rustc-env=CARGO_FEATURE_<name>=1")`. Without it, the tests are never
+///    generated. With `attach`, an empty string is generated when the feature is disabled.
+///  * `main_returns = "..."`: wraps the rust code blocks without a `fn main` into a
+///    `fn main() -> <type>` so they can use `?`. If the type is a `Result`, `Ok(())` is added at
+///    the end of the code block unless its last line already starts with `Ok` or `return`. For
+///    other types (like `std::process::ExitCode`), the code block must return the value itself.
+///  * `no_std`: adds hidden `#![no_std]` and `extern crate alloc;` lines to the rust code blocks
+///    which aren't already `#![no_std]`. rustdoc makes a crate out of each code block, so it
+///    applies to each of them separately. Since the test needs to run, `std` is still linked with
+///    a hidden `extern crate std;`, but nothing from the `std` prelude can be used: the examples
+///    must use `core` and `alloc`.
+///  * `extern_crate = "..."`: adds a hidden `extern crate <name>;` line at the top of the rust
+///    code blocks which don't already have it. Code blocks in the 2015 edition need it to use
+///    another crate than the one being tested.
+///  * `expose_clean = NAME`: generates a `pub const NAME: &str` as well, containing the file
+///    content without the lines hidden by rustdoc in the rust code blocks (the ones starting with
+///    `# `) and with their info string reduced to `rust` (`no_run`, `should_panic`... are dropped).
+///    It allows to reuse the text somewhere else without the testing lines. With
+///    `strip_comments`, the `//` comments are removed from the rust code blocks as well (the
+///    tested documentation keeps them).
+///  * `expose_numbered = NAME`: same as `expose_clean` (`strip_comments` included) but the lines
+///    of the code blocks are prefixed with their number (like `1 | let x = 1;`), for a renderer
+///    wanting numbered listings. The tested documentation isn't numbered.
+///  * `expose_len = NAME`: generates a `pub const NAME: usize` as well, containing the length in
+///    bytes of the documentation (the file content with the changes made by the other options),
+///    so a test can check a generated file isn't empty or too big.
+///
+/// You can also generate one module per code block modifier with the `[modifier => name]` syntax.
+/// `modifier` is added to all the rust code blocks of the file, unless it's `run`, in which case
+/// they are kept as is. Supported modifiers are `run`, `no_run`, `ignore`, `should_panic` and
+/// `compile_fail`:
+///
+/// ```edition2018,no_run
+/// doc_comment::doctest! { "../README.md", [run => readme_run], [no_run => readme_no_run] }
+/// # fn main() {}
+/// ```
+///
+/// To test a file on some platforms (or configurations) only, `variants = [(predicate, name), ...]`
+/// generates a `#[cfg(predicate)] mod name {}` for each pair:
+///
+/// ```edition2018,no_run
+/// doc_comment::doctest! {
+///     "../README.md",
+///     variants = [(unix, readme_unix), (windows, readme_windows)],
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Flags can be given the same way:
+///
+///  * `native`: instead of reading the file, generates `#[doc = include_str!("...")]`. The file
+///    content is then rendered and tested exactly as written. It requires a rust version
+///    supporting `include_str!` in `#[doc]` attributes (1.54 or newer) and cannot be combined
+///    with options modifying the file content.
+///
+///  * `register`: generates a `#[doc(hidden)] pub const __DOCTEST_FILE_<path>_<hash>: &str`
+///    containing the file path, `<path>` being the path in uppercase with all non-alphanumeric
+///    characters replaced with `_` and `<hash>` a hash of the path (so `a-b.md` and `a_b.md` don't
+///    conflict). It allows external tools to list the files tested with this macro.
+///  * `quiet_track`: the constants including the file (so your crate is rebuilt when it's
+///    updated) are put in a hidden `doc_comment_file_tracking` module, which makes them
+///    easier to tell apart in `cargo expand` output.
+///  * `lint_prompts`: emits an error if a line of a rust code block starts with `$ ` or `> `. It
+///    usually means that a shell command was written in a code block without a language (and
+///    therefore considered as rust code).
+///  * `ranges_from = "..."`: path of a file listing the line ranges of the file to test, one
+///    `start..end` (or `start..=end`) range per line, lines starting at 1. The other lines are
+///    replaced with empty lines so the line numbers don't change.
+///  * `mdbook_includes`: replaces the mdBook `{{#include path}}` directives with the content of
+///    the file, relative to the file containing the directive. It's done recursively.
+///    `{{#include path:name}}` only includes the lines between the `ANCHOR: name` and
+///    `ANCHOR_END: name` comments of the file (without the lines of the other anchors).
+///  * `markers`: only keeps the parts of the file between `<!-- doctest:start -->` and
+///    `<!-- doctest:end -->` comments, so only the curated examples of a big document are tested.
+///    There can be multiple parts, they're separated with an empty line. `markers = "..."` uses
+///    another name than `doctest` in the comments.
+///  * `anchor = "..."`: only tests the lines between the `ANCHOR: <name>` and
+///    `ANCHOR_END: <name>` comments of the file (like `// ANCHOR: example`), as a rust code
+///    block. It allows to keep examples in real `.rs` files.
+///  * `fence_as = "..."`: puts the whole file in a code block with the given info string (like
+///    `rust` or `rust,no_run`), to test a source file which is also a standalone program.
+///    `hide_lines = "..."` hides the given comma-separated lines (like `1..3,10`, lines starting at
+///    1 and ranges being written like in `ranges_from`) by adding `# ` at their beginning.
+///  * `strip_front_matter`: removes the YAML (`---`) or TOML (`+++`) front matter at the
+///    beginning of the file.
+///  * `preserve_lines`: the lines removed by `strip_front_matter` and `from_rust` are replaced
+///    with empty lines so the line numbers in the test failures match the ones of the file
+///    (`prepend` still shifts them).
+///  * `edition2015`, `edition2018` and `edition2021`: adds this edition to the rust code blocks
+///    which don't have one yet.
+///  * `block_attrs = "..."`: comma-separated modifiers (like `should_panic` or `no_run`) added
+///    to all the rust code blocks of the file. The modifiers a code block already has aren't
+///    duplicated.
+///  * `add_modifier = "..."` and `to = "..."`: adds the `add_modifier` modifier only to the rust
+///    code blocks which already have the `to` modifier. For example,
+///    `add_modifier = "no_run", to = "should_panic"` doesn't run the code blocks which panic.
+///  * `dedup_blocks`: only tests the first of identical rust code blocks (ignoring trailing
+///    whitespace), the other ones are turned into `text` blocks. It's useful for generated files.
+///  * `source_markers`: adds a hidden `// source: <path>:<line>` comment at the beginning of
+///    each rust code block so tools reading the test failures can find where the code comes
+///    from. The line numbers are the ones of the tested content, so they don't match the file
+///    if `prepend` is used (or if lines are removed without `preserve_lines`).
+///  * `expect_all_fail`: emits an error if one of the rust code blocks isn't `compile_fail` (or
+///    if there is none). It's useful for files showing what doesn't compile.
+///  * `report_skipped`: emits a warning listing the code blocks rustdoc won't test (the ones
+///    which are `ignore`d or aren't rust code). Since proc-macros cannot emit warnings on stable,
+///    it's a deprecation warning.
+///  * `strict`: same as `report_skipped` but emits an error instead.
+///  * `syntax_check`: emits an error if a tested rust code block cannot even be tokenized, so
+///    grossly malformed examples (unbalanced delimiters, unclosed string literals or comments)
+///    are found by `cargo build`, without waiting for `cargo test --doc`. It doesn't parse the
+///    code: the other syntax errors (like a missing `;`) are still only found by rustdoc. The `ignore`d and
+///    `compile_fail` code blocks aren't checked.
+///  * `non_empty`: emits an error if the file is empty or only contains whitespace (after
+///    `strip_front_matter`), which usually means that the step generating it failed.
+///  * `lint_mixed_indent`: emits an error if a line of a rust code block is indented with both
+///    tabs and spaces.
+///  * `check_relative_links`: emits an error if a link of the file points to a relative path (like
+///    `[setup](setup.md)`) which doesn't exist, relative to the directory of the file. Links with
+///    a scheme (`https://`...), absolute paths and anchors alone aren't checked. With
+///    `check_relative_links = "warn"`, a (deprecation) warning is emitted for each broken link
+///    instead.
+///  * `print_modes`: emits a (deprecation) warning listing the code blocks of the file with their
+///    line, their info string and what rustdoc does with them (run, only compiled, ignored...).
+///    It's an error with `strict`, to look at it without having to build everything else. It only
+///    changes what is reported: the file is tested as usual.
+///  * `expect_crate = "..."` and `forbidden_crates = [...]`: emits an error if a rust code block
+///    uses one of the `forbidden_crates` (with a `name::` path or an `extern crate name`) and a
+///    warning if none uses `expect_crate`. It's useful to find the examples which weren't updated
+///    after renaming a crate.
+///  * `debug_path`: emits an error with the absolute paths the file paths resolve to, instead of
+///    generating anything. It's useful to understand why a file isn't found or why the wrong one
+///    is tested.
+///  * `allow_unknown`: ignores the flags and options this version of `doctest` doesn't know
+///    instead of emitting an error, so a crate can use newer ones while still building with older
+///    versions of `doc-comment`. The first unknown identifier is still the test name.
+///  * `split`: generates one module per rust code block instead of one for the whole file, so
+///    each code block can be run with `cargo test --doc <module>`. The modules are named
+///    `<test name>_<n>` (`block_<n>` without a test name), `<n>` starting at 1. With
+///    `stable_names`, `<n>` is replaced with a short hash of the code (like `block_1f2e3d`) so
+///    adding or moving a code block doesn't rename the other modules.
+///  * `build_up = NAME`: concatenates the rust code blocks into a single one, documenting
+///    `mod NAME`, instead of testing each of them separately. Later code blocks can then use what
+///    the previous ones defined, which is what a tutorial building an example step by step needs.
+///    The downside is that the code blocks aren't independent anymore: a failure in one of them
+///    fails the whole test and nothing tells which code block it comes from. The `compile_fail`
+///    and `ignore`d code blocks are left out, and only the rust code is kept: the text around it
+///    isn't part of the documentation anymore. The code blocks are never compiled as part of the
+///    crate, so their items cannot be used (or re-exported) outside of the doctest.
+///  * `crate_suffix`: prefixes the module name(s) with the crate name (from the `CARGO_PKG_NAME`
+///    environment variable set by cargo). Without a test name, the file name is used. For
+///    example, `doctest!("../README.md", crate_suffix)` generates `mod my_crate_readme {}`. It
+///    allows to know which crate a failing test comes from in a workspace.
+///  * `auto_name`: names the module after the file name (without its extension, and with the
+///    characters which cannot be in an identifier replaced with `_`), like with a test name. For
+///    example, `doctest!("../docs/Quick-Start.md", auto_name)` generates `mod quick_start {}`.
+///    Since rustdoc names the doctests after the path of the item, they can then be selected with
+///    `cargo test --doc quick_start`.
+///  * `lit = "..."`: content added as is. It can be given multiple times and mixed with multiple
+///    file paths: everything is concatenated in the order it's written. For example,
+///    `doctest!(lit = "# Overview\n\n", "../README.md")`.
+///  * `header`: adds a ``*From `<path>`*`` line at the beginning of the documentation.
+///    `header_strip = N` removes the first `N` components of the displayed path (none by default),
+///    the file name is always kept.
+///  * `must_match = "..."`: emits an error if the given file doesn't have the same content as the
+///    tested one, for the copies which must stay in sync (like a `README.md` at the root of a
+///    workspace and in a crate). The files are compared as written (indentation and line endings
+///    included) and the first line which differs is given in the error.
+///  * `source_comment`: adds a `<!-- doctest-source: <path> -->` HTML comment at the very top of
+///    the documentation (one per file with `lit` or `join`). It isn't rendered but tools
+///    processing the HTML generated by rustdoc can use it to link back to the source file.
+///  * `footer`: adds a `_Examples verified by doc-comment vX.Y.Z_` line at the end of the
+///    documentation. `footer = "..."` uses the given text instead.
+///  * `details = "..."`: puts the documentation into a collapsed `<details>` block, with the
+///    given text as summary. The code blocks are still tested.
+///  * `once`: only generates the tests the first time the file is included with `once` in the
+///    crate, so copy-pasted `doctest!` calls don't run the same tests twice. It's best-effort:
+///    the included files are remembered by the proc-macro while it's loaded, which rustc doesn't
+///    specify. Which of the calls generates the tests isn't guaranteed, and a tool keeping the
+///    proc-macro loaded between expansions (like an IDE) may generate the tests more than once or
+///    not at all.
+///  * `optional`: generates nothing if the file doesn't exist instead of emitting an error. It's
+///    useful for generated files which aren't always there. Since nothing tracks the missing
+///    file, the crate isn't rebuilt when it's created, you need to run `cargo clean -p <crate>`.
+///  * `cfg_doctest`: adds `#[cfg(doctest)]` on the generated item(s), so they only exist when
+///    rustdoc looks for tests. It's the same as writing `#[cfg(doctest)]` before `doctest!`,
+///    except for the items generated by `expose_clean` and `register` which are always there.
+///  * `hidden`: adds `#[doc(hidden)]` on the generated item(s), so they don't appear in the
+///    documentation (with `--document-private-items` or with a `pub` holder). rustdoc still tests
+///    the code blocks of hidden items.
+///  * `attach`: only generates the documentation string, so you can put it on your own item
+///    with `#[doc = doctest!(...)]`. Unless `native` is used as well, the item won't be rebuilt
+///    when the file is updated.
+///
+/// By default, the documentation is put on an `extern {}` block, which is the least noisy item
+/// to generate. If you give a test name, it's put on a `mod` with this name instead so the test
+/// names say where they come from. Use `attach` when you already have an item where the
+/// documentation makes sense.
+///
+/// ```edition2018,no_run
+/// doc_comment::doctest! { "../tests/fixtures/tabs.md", tab_width = 4 }
+/// doc_comment::doctest! { "../tests/fixtures/skip.md", skip_containing = ["panic!", "todo!"] }
+/// doc_comment::doctest! { "../tests/fixtures/latin1.md", encoding = "latin1" }
+/// doc_comment::doctest! { from_rust = "../tests/fixtures/demo.rs", demo }
+/// doc_comment::doctest! {
+///     data = "../tests/fixtures/options.toml",
+///     template = "../tests/fixtures/options.md.tmpl",
+/// }
+/// doc_comment::doctest! {
+///     "../tests/fixtures/question_mark.md",
+///     main_returns = "Result<(), std::num::ParseIntError>",
+/// }
+/// doc_comment::doctest! { base_dir = "../tests/fixtures", files = ["tabs.md", "body.md"] }
+/// // Generates the `guide`, `guide_fr` and `guide_de` modules.
+/// doc_comment::doctest! {
+///     base_dir = "../tests/fixtures",
+///     base = "guide",
+///     locales = ["", "fr", "de"],
+/// }
+/// doc_comment::doctest! {
+///     "../tests/fixtures/body.md",
+///     prepend = "../tests/fixtures/header.md",
+///     append = "../tests/fixtures/footer.md",
+/// }
+/// doc_comment::doctest! { "../README.md", native }
+///
+/// #[doc = doc_comment::doctest!("../README.md", attach)]
+/// pub struct Foo;
+/// # fn main() {}
+/// ```
+///
+/// ```edition2018
+/// doc_comment::doctest! { "../tests/fixtures/hidden.md", expose_clean = CLEAN }
+///
+/// assert!(!CLEAN.contains("# fn main"));
+/// ```
+///
+/// ```edition2018
+/// doc_comment::doctest! { "../tests/fixtures/tabs.md", register }
+///
+/// assert_eq!(__DOCTEST_FILE_TESTS_FIXTURES_TABS_MD_113AA126, "../tests/fixtures/tabs.md");
+/// ```
+#[proc_macro]
+pub fn doctest(item: TokenStream) -> TokenStream {
+    match expand_doctest(item) {
+        Ok(tokens) => tokens,
+        Err(e) => e.into_compile_error(),
+    }
+}
+
+fn expand_doctest(item: TokenStream) -> Result<TokenStream, Error> {
+    let mut parts = item.into_iter().peekable();
+    let mut file_path = None;
+    let mut file_span = Span::call_site();
+    let mut test_name = None;
+    let mut variants = Vec::new();
+    let mut pieces = Vec::new();
+    let mut has_lit = false;
+    let mut extra_path_span = None;
+    let mut options = DoctestOptions::default();
+
+    loop {
+        match parts.next() {
+            Some(TokenTree::Literal(l)) => {
+                let l_s = l.to_string();
+                // The paths are given to the file system (and escaped again when they're put in
+                // the generated code), so `"C:\\Users"` becomes `C:\Users`.
+                let path = literal_value(&TokenTree::Literal(l.clone()))?;
+                if file_path.is_some() {
+                    // Only allowed if `lit` is used, which is checked once everything is parsed.
+                    match path {
+                        Some(path) => {
+                            extra_path_span.get_or_insert((l.span(), l_s));
+                            pieces.push(Piece::File(path));
+                            continue;
+                        }
+                        None => {
+                            return Err(Error::new(
+                                l.span(),
+                                format!("expected an identifier, found `{}`", l_s),
+                            ))
+                        }
+                    }
+                }
+                let path = match path {
+                    Some(path) => path,
+                    None => {
+                        return Err(Error::new(
+                            l.span(),
+                            format!(
+                                "First parameter of doctest should be a string literal, found `{}`",
+                                l_s
+                            ),
+                        ))
+                    }
+                };
+                file_path = Some(path.clone());
+                pieces.push(Piece::File(path));
+                file_span = l.span();
+            }
+            Some(TokenTree::Punct(ref p)) if p.to_string() == "," => {}
+            Some(TokenTree::Ident(i)) => {
+                let i_s = i.to_string();
+                match parts.peek() {
+                    Some(TokenTree::Punct(p)) if p.as_char() == '=' => {
+                        parts.next();
+                        let value = take_value(&mut parts);
+                        if i_s == "from_rust" || i_s == "from_rust_all" || i_s == "template" {
+                            file_span = value_span(&i, &value);
+                        }
+                        if i_s == "lit" {
+                            pieces.push(Piece::Lit(value_as_str(&i, &value)?));
+                            has_lit = true;
+                        } else {
+                            options.set(&i, value)?;
+                        }
+                    }
+                    _ => {
+                        if file_path.is_none()
+                            && options.from_rust.is_none()
+                            && options.from_rust_all.is_none()
+                            && options.template.is_none()
+                            && options.base.is_none()
+                            && options.files.is_empty()
+                        {
+                            return Err(Error::new(
+                                i.span(),
+                                format!(
+                                    "First parameter of doctest should be a string literal, \
+                                     found ident `{}`",
+                                    i_s
+                                ),
+                            ));
+                        }
+                        if options.set_flag(&i_s) {
+                            continue;
+                        }
+                        if is_keyword(&i_s) {
+                            return Err(Error::new(
+                                i.span(),
+                                format!("expected an identifier, found keyword `{}`", i_s),
+                            ));
+                        }
+                        match parts.peek() {
+                            Some(TokenTree::Punct(p)) if p.as_char() == ',' => {}
+                            None => {}
+                            Some(t) => {
+                                return Err(Error::new(
+                                    t.span(),
+                                    format!("expected an identifier, found `{}`", t),
+                                ));
+                            }
+                        }
+                        if let Some(ref name) = test_name {
+                            options.unknown.push(Error::new(
+                                i.span(),
+                                format!(
+                                    "Unknown doctest flag `{}` (the test name is already `{}`)",
+                                    i_s, name
+                                ),
+                            ));
+                            continue;
+                        }
+                        test_name = Some(i_s);
+                    }
+                }
+            }
+            Some(TokenTree::Group(ref g)) if g.delimiter() == Delimiter::Bracket => {
+                variants.push(parse_variant(g)?);
+            }
+            Some(t) => return Err(Error::new(t.span(), format!("Unexpected token `{}`", t))),
+            None => break,
+        }
+    }
+    if !options.allow_unknown && !options.unknown.is_empty() {
+        return Err(options.unknown.remove(0));
+    }
+    if let Some(ref base_dir) = options.base_dir {
+        let full_path = resolve_path(Path::new(base_dir));
+        if !full_path.is_dir() {
+            return Err(Error::call_site(format!(
+                "`base_dir` must be a directory, `{}` isn't",
+                full_path.display()
+            )));
+        }
+    }
+    match options.base.take() {
+        Some(base) => {
+            if options.locales.is_empty() {
+                return Err(Error::call_site("`base` requires `locales`".to_owned()));
+            }
+            let ext = options.ext.take().unwrap_or_else(|| "md".to_owned());
+            for locale in &options.locales {
+                let file = if locale.is_empty() {
+                    format!("{}.{}", base, ext)
+                } else {
+                    format!("{}.{}.{}", base, locale, ext)
+                };
+                let full_path = resolve_path(&options.path(&file));
+                if !full_path.is_file() {
+                    return Err(Error::call_site(format!(
+                        "No file for the locale `{}` of `{}`: `{}` doesn't exist",
+                        locale,
+                        base,
+                        full_path.display()
+                    )));
+                }
+                options.files.push(file);
+            }
+        }
+        None if !options.locales.is_empty() || options.ext.is_some() => {
+            return Err(Error::call_site(
+                "`locales` and `ext` require `base`".to_owned(),
+            ));
+        }
+        None => {}
+    }
+    if let Some(ref order) = options.order {
+        if options.files.is_empty() && options.join.is_empty() {
+            return Err(Error::call_site(
+                "`order` requires `files` or `join`".to_owned(),
+            ));
+        }
+        if order == "sorted" {
+            options.files.sort();
+            options.join.sort();
+        }
+    }
+    if options.separator.is_some() && options.join.is_empty() {
+        return Err(Error::call_site("`separator` requires `join`".to_owned()));
+    }
+    if options.join.is_empty() != options.into.is_none() {
+        return Err(Error::call_site(
+            "`join` and `into` must be used together".to_owned(),
+        ));
+    }
+    if !options.join.is_empty() {
+        if file_path.is_some() || has_lit || !options.files.is_empty() {
+            return Err(Error::call_site(
+                "`join` cannot be used with file paths, `lit` or `files`".to_owned(),
+            ));
+        }
+        if test_name.is_some() {
+            return Err(Error::call_site(
+                "`join` cannot be used with a test name (the module is named with `into`)"
+                    .to_owned(),
+            ));
+        }
+        let separator = options
+            .separator
+            .clone()
+            .unwrap_or_else(|| "\n\n".to_owned());
+        for (nb, file) in options.join.iter().enumerate() {
+            if nb > 0 {
+                options.pieces.push(Piece::Lit(separator.clone()));
+            }
+            options.pieces.push(Piece::File(file.clone()));
+        }
+        file_path = options.join.first().cloned();
+        test_name = options.into.clone();
+    } else if has_lit {
+        if file_path.is_none() {
+            return Err(Error::call_site(
+                "`lit` can only be used with file paths".to_owned(),
+            ));
+        }
+        options.pieces = pieces;
+    } else if let Some((span, path)) = extra_path_span {
+        return Err(Error::new(
+            span,
+            format!("expected an identifier, found `{}`", path),
+        ));
+    }
+    if options.add_modifier.is_some() != options.to.is_some() {
+        return Err(Error::call_site(
+            "`add_modifier` and `to` must be used together".to_owned(),
+        ));
+    }
+    if options.strip_comments && options.expose_clean.is_none() && options.expose_numbered.is_none()
+    {
+        return Err(Error::call_site(
+            "`strip_comments` requires `expose_clean` or `expose_numbered`".to_owned(),
+        ));
+    }
+    if options.build_up.is_some() && options.split {
+        return Err(Error::call_site(
+            "`build_up` cannot be used with `split`".to_owned(),
+        ));
+    }
+    if options.stable_names && !options.split {
+        return Err(Error::call_site(
+            "`stable_names` requires `split`".to_owned(),
+        ));
+    }
+    if options.on_oversize.is_some() && options.max_file_size.is_none() {
+        return Err(Error::call_site(
+            "`on_oversize` requires `max_file_size`".to_owned(),
+        ));
+    }
+    if options.data.is_some() != options.template.is_some() {
+        return Err(Error::call_site(
+            "`data` and `template` must be used together".to_owned(),
+        ));
+    }
+    if !options.hide_lines.is_empty() && options.fence_as.is_none() {
+        return Err(Error::call_site(
+            "`hide_lines` requires `fence_as`".to_owned(),
+        ));
+    }
+    if options.fence_as.is_some() && (options.anchor.is_some() || options.from_rust.is_some()) {
+        return Err(Error::call_site(
+            "`fence_as` cannot be used with `anchor` or `from_rust`".to_owned(),
+        ));
+    }
+    if options.anchor.is_some() && options.from_rust.is_some() {
+        return Err(Error::call_site(
+            "`anchor` cannot be used with `from_rust`".to_owned(),
+        ));
+    }
+    if options.template.is_some() && options.from_rust.is_some() {
+        return Err(Error::call_site(
+            "`template` cannot be used with `from_rust`".to_owned(),
+        ));
+    }
+    if options.from_rust_all.is_some()
+        && (options.from_rust.is_some()
+            || options.rewrites_content()
+            || options.split
+            || options.build_up.is_some()
+            || options.attach
+            || !variants.is_empty())
+    {
+        return Err(Error::call_site(
+            "`from_rust_all` cannot be used with `from_rust`, options modifying the content, \
+             `split`, `build_up`, `attach` or variants"
+                .to_owned(),
+        ));
+    }
+    let source_file = options
+        .from_rust
+        .clone()
+        .or_else(|| options.from_rust_all.clone())
+        .or_else(|| options.template.clone());
+    let file_path = match (file_path, source_file) {
+        (Some(_), Some(_)) => {
+            return Err(Error::call_site(format!(
+                "`{}` cannot be used with a file path",
+                if options.from_rust.is_some() {
+                    "from_rust"
+                } else if options.from_rust_all.is_some() {
+                    "from_rust_all"
+                } else {
+                    "template"
+                }
+            )))
+        }
+        (Some(_), None) | (None, Some(_)) if !options.files.is_empty() => {
+            return Err(Error::call_site(
+                "`files` cannot be used with a file path".to_owned(),
+            ))
+        }
+        (Some(path), None) | (None, Some(path)) => path,
+        (None, None) if !options.files.is_empty() => String::new(),
+        (None, None) => {
+            return Err(Error::call_site(
+                "doctest expects at least one parameter".to_owned(),
+            ))
+        }
+    };
+    if options.auto_name {
+        if test_name.is_some() || !options.files.is_empty() {
+            return Err(Error::call_site(
+                "`auto_name` cannot be used with a test name or with `files`".to_owned(),
+            ));
+        }
+        let name = stem_ident(Path::new(&file_path));
+        // `mod.md` cannot give `mod mod {}`.
+        test_name = Some(if is_keyword(&name) {
+            format!("{}_", name)
+        } else {
+            name
+        });
+    }
+    if options.crate_suffix {
+        let krate =
+            match std::env::var("CARGO_PKG_NAME") {
+                Ok(name) => sanitize_ident(&name).to_lowercase(),
+                Err(_) => return Err(Error::call_site(
+                    "`crate_suffix` requires the `CARGO_PKG_NAME` environment variable (which is \
+                     set by cargo)"
+                        .to_owned(),
+                )),
+            };
+        for (_, name) in &mut variants {
+            *name = format!("{}_{}", krate, name);
+        }
+        test_name = match test_name {
+            Some(name) => Some(format!("{}_{}", krate, name)),
+            // The file names are added after it.
+            None if !options.files.is_empty() => Some(krate),
+            None if variants.is_empty() => {
+                Some(format!("{}_{}", krate, stem_ident(Path::new(&file_path))))
+            }
+            None => None,
+        };
+    }
+    let mut out = String::new();
+    if let Some(ref feature) = options.require_feature {
+        let var = format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"));
+        if !options.attach {
+            // `option_env!` makes the compiler rebuild the crate when the variable changes.
+            out.push_str(&format!(
+                "const _: Option<&'static str> = option_env!(\"{}\");",
+                escape_content(&var)
+            ));
+        }
+        if std::env::var_os(&var).is_none() {
+            if options.attach {
+                out.push_str("\"\"");
+            }
+            return parse_generated(&out);
+        }
+    }
+    if options.files.is_empty() {
+        let path = options.path(&file_path);
+        out.push_str(&expand_file(
+            &path,
+            file_span,
+            test_name.as_ref(),
+            &variants,
+            &options,
+        )?);
+        return parse_generated(&out);
+    }
+    if options.attach || !variants.is_empty() {
+        return Err(Error::call_site(
+            "`files` cannot be used with `attach` or variants".to_owned(),
+        ));
+    }
+    if options.expose_clean.is_some() || options.expose_numbered.is_some() {
+        return Err(Error::call_site(
+            "`files` cannot be used with `expose_clean` or `expose_numbered`".to_owned(),
+        ));
+    }
+    for file in &options.files {
+        let path = options.path(file);
+        let stem = Path::new(file)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| file.clone());
+        let name = match test_name {
+            Some(ref prefix) => format!("{}_{}", prefix, sanitize_ident(&stem).to_lowercase()),
+            None => sanitize_ident(&stem).to_lowercase(),
+        };
+        out.push_str(&expand_file(&path, file_span, Some(&name), &[], &options)?);
+    }
+    parse_generated(&out)
+}
+
+/// Generates the documented item(s) for the file at `file_path`.
+fn expand_file(
+    file_path: &Path,
+    file_span: Span,
+    test_name: Option<&String>,
+    variants: &[(Option<String>, String)],
+    options: &DoctestOptions,
+) -> Result<String, Error> {
+    if options.debug_path {
+        let paths = Some(file_path)
+            .into_iter()
+            .chain(options.prepend.iter().map(Path::new))
+            .chain(options.append.iter().map(Path::new))
+            .map(|path| {
+                let full_path = resolve_path(path);
+                format!(
+                    "`{}` resolves to `{}`{}",
+                    path.display(),
+                    full_path.display(),
+                    if full_path.exists() {
+                        ""
+                    } else {
+                        " (which doesn't exist)"
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        return Err(Error::new(file_span, paths.join("\n")));
+    }
+    if options.once && !first_inclusion(file_path) {
+        let mut out = String::new();
+        if options.attach && !options.attribute {
+            out.push_str("\"\"");
+        } else {
+            track_file("include_str", &tracked_path(file_path), &mut out);
+            if options.quiet_track {
+                out = hide_tracking(&out);
+            }
+        }
+        return Ok(out);
+    }
+    if options.skip_missing(file_path) || options.skip_oversized(file_path, file_span)? {
+        return Ok(if options.attach && !options.attribute {
+            "\"\"".to_owned()
+        } else {
+            String::new()
+        });
+    }
+    let mut includes = String::new();
+    options.check_match(file_path, file_span, &mut includes)?;
+    let content = if options.native {
+        if options.rewrites_content() || variants.iter().any(|(m, _)| m.is_some()) {
+            return Err(Error::call_site(
+                "`native` cannot be used with options modifying the file content".to_owned(),
+            ));
+        }
+        if options.report_skipped
+            || options.strict
+            || options.print_modes
+            || options.syntax_check
+            || options.lint_mixed_indent
+            || options.non_empty
+            || options.check_links
+        {
+            return Err(Error::call_site(
+                "`native` cannot be used with `report_skipped`, `strict`, `print_modes`, \
+                 `syntax_check`, `lint_mixed_indent`, `non_empty` or `check_relative_links`"
+                    .to_owned(),
+            ));
+        }
+        if options.expect_crate.is_some() || !options.forbidden_crates.is_empty() {
+            return Err(Error::call_site(
+                "`native` cannot be used with `expect_crate` or `forbidden_crates`".to_owned(),
+            ));
+        }
+        if options.split || options.build_up.is_some() || options.from_rust_all.is_some() {
+            return Err(Error::call_site(
+                "`native` cannot be used with `split`, `build_up` or `from_rust_all`".to_owned(),
+            ));
+        }
+        if options.via.is_some() {
+            return Err(Error::call_site(
+                "`native` cannot be used with `via`".to_owned(),
+            ));
+        }
+        if options.lint_prompts || !options.lint_templates.is_empty() {
+            return Err(Error::call_site(
+                "`native` cannot be used with `lint_prompts` or `lint_templates`".to_owned(),
+            ));
+        }
+        if options.expect_all_fail {
+            return Err(Error::call_site(
+                "`native` cannot be used with `expect_all_fail`".to_owned(),
+            ));
+        }
+        check_exists(file_path, file_span)?;
+        check_not_dir(&resolve_path(file_path), file_span)?;
+        record_file(&resolve_path(file_path), file_span)?;
+        track_file("include_str", &tracked_path(file_path), &mut includes);
+        None
+    } else {
+        let content = options.read_content(file_path, file_span, &mut includes)?;
+        options.check_non_empty(&content, file_path, file_span)?;
+        options.report_unignored(&content, file_path, &mut includes);
+        let mut content = options.apply(content);
+        options.lint(&content, file_path, file_span)?;
+        options.check_all_fail(&content, file_path, file_span)?;
+        options.check_syntax(&content, file_path, file_span)?;
+        options.check_indent(&content, file_path, file_span)?;
+        options.check_links(&content, file_path, file_span, &mut includes)?;
+        if options.header {
+            content = format!(
+                "*From `{}`*\n\n{}",
+                strip_components(file_path, options.header_strip),
+                content
+            );
+        }
+        if let Some(ref summary) = options.details {
+            // Without the empty lines, rustdoc wouldn't render the markdown inside the tags.
+            content = format!(
+                "<details><summary>{}</summary>\n\n{}\n\n</details>\n",
+                escape_html(summary),
+                content
+            );
+        }
+        if let Some(ref footer) = options.footer {
+            content = add_footer(&content, footer);
+        }
+        if options.source_comment {
+            // Added last so it's at the very top.
+            let mut comments = String::new();
+            for path in options.main_paths(file_path) {
+                comments.push_str(&format!("<!-- doctest-source: {} -->\n", path.display()));
+            }
+            content.insert_str(0, &comments);
+        }
+        if options.source_markers {
+            content = add_source_markers(&content, file_path);
+        }
+        options.print_modes(&content, file_path, file_span, &mut includes)?;
+        options.report_skipped(&content, file_path, file_span, &mut includes)?;
+        options.check_crate_names(&content, file_path, file_span, &mut includes)?;
+        Some(content)
+    };
+    if options.quiet_track {
+        includes = hide_tracking(&includes);
+    }
+    if let Some(ref name) = options.expose_clean {
+        let content = match content {
+            Some(ref content) => content,
+            None => {
+                return Err(Error::call_site(
+                    "`expose_clean` cannot be used with `native`".to_owned(),
+                ))
+            }
+        };
+        includes.push_str(&format!(
+            "pub const {}: &str = \"{}\";",
+            name,
+            escape_content(&clean_content(content, options.strip_comments))
+        ));
+    }
+    if let Some(ref name) = options.expose_numbered {
+        let content = match content {
+            Some(ref content) => content,
+            None => {
+                return Err(Error::call_site(
+                    "`expose_numbered` cannot be used with `native`".to_owned(),
+                ))
+            }
+        };
+        // The hidden lines aren't displayed so they aren't numbered either.
+        let numbered = rewrite_fences(
+            &clean_content(content, options.strip_comments),
+            |_, body| *body = number_lines(body),
+        );
+        includes.push_str(&format!(
+            "pub const {}: &str = \"{}\";",
+            name,
+            escape_content(&numbered)
+        ));
+    }
+    if let Some(ref name) = options.expose_len {
+        let content = match content {
+            Some(ref content) => content,
+            None => {
+                return Err(Error::call_site(
+                    "`expose_len` cannot be used with `native`".to_owned(),
+                ))
+            }
+        };
+        includes.push_str(&format!("pub const {}: usize = {};", name, content.len()));
+    }
+    let doc_str = |content: &str| match options.via {
+        Some(ref via) => [via, "!(\"", &escape_content(content), "\")"].concat(),
+        None => ["\"", &escape_content(content), "\""].concat(),
+    };
+    let doc_for = |modifier: Option<&str>| match content {
+        Some(ref content) => match modifier {
+            Some(modifier) => doc_str(&add_block_attrs(content, &[modifier])),
+            None => doc_str(content),
+        },
+        None => format!(
+            "include_str!(\"{}\")",
+            escape_content(&tracked_path(file_path).display().to_string())
+        ),
+    };
+    if options.register {
+        let path = file_path.display().to_string();
+        includes.push_str(&format!(
+            "#[doc(hidden)] pub const __DOCTEST_FILE_{}_{:08X}: &str = \"{}\";",
+            sanitize_ident(&path).to_uppercase(),
+            fnv1a(&path),
+            escape_content(&path),
+        ));
+    }
+    if !options.cfg_variants.is_empty()
+        && (options.attach
+            || options.split
+            || options.build_up.is_some()
+            || options.from_rust_all.is_some())
+    {
+        return Err(Error::call_site(
+            "`variants` cannot be used with `attach`, `split`, `build_up` or `from_rust_all`"
+                .to_owned(),
+        ));
+    }
+    if options.attach {
+        if test_name.is_some() || !variants.is_empty() {
+            return Err(Error::call_site(
+                "`attach` cannot be used with a test name".to_owned(),
+            ));
+        }
+        if options.cfg_doctest || options.hidden {
+            return Err(Error::call_site(
+                "`cfg_doctest` and `hidden` cannot be used with `attach`".to_owned(),
+            ));
+        }
+        if options.attribute {
+            // The items are put before so the `#[doc]` attribute applies to the annotated item.
+            return Ok([&includes, "#[doc = ", &doc_for(None), "]"].concat());
+        }
+        if options.expose_clean.is_some()
+            || options.expose_numbered.is_some()
+            || options.expose_len.is_some()
+        {
+            return Err(Error::call_site(
+                "`expose_clean`, `expose_numbered` and `expose_len` cannot be used with `attach`"
+                    .to_owned(),
+            ));
+        }
+        return Ok(doc_for(None));
+    }
+    let is_named = test_name.is_some()
+        || !variants.is_empty()
+        || !options.cfg_variants.is_empty()
+        || options.split
+        || options.build_up.is_some()
+        || options.from_rust_all.is_some();
+    let holder_span = options.holder_span.unwrap_or_else(Span::call_site);
+    let holder = match options.holder {
+        Some(Holder::Extern) if is_named => {
+            return Err(Error::new(
+                holder_span,
+                "`holder = \"extern\"` cannot be used with a test name".to_owned(),
+            ))
+        }
+        Some(holder) if holder != Holder::Extern && !is_named => {
+            return Err(Error::new(
+                holder_span,
+                "`holder` requires a test name unless it's `\"extern\"`".to_owned(),
+            ))
+        }
+        Some(holder) => holder,
+        None if is_named => Holder::Mod,
+        None => Holder::Extern,
+    };
+    let attrs = options.holder_attrs();
+    let documented = |doc: String, name: &str| {
+        [&attrs, "#[doc = ", &doc, "]\n", &holder.item(name), "\n"].concat()
+    };
+    let mut out = String::new();
+    if options.split {
+        if !variants.is_empty() {
+            return Err(Error::call_site(
+                "`split` cannot be used with variants".to_owned(),
+            ));
+        }
+        // `native` is rejected before so the content is always there.
+        let content = content.as_ref().map(|c| c.as_str()).unwrap_or("");
+        let fences = scan_fences(content)
+            .into_iter()
+            .filter(|f| is_rust_fence(f.info))
+            .collect::<Vec<_>>();
+        let bodies = fences.iter().map(|f| f.body).collect::<Vec<_>>();
+        let prefix = test_name.map(|t| t.as_str()).unwrap_or("block");
+        for (fence, name) in fences
+            .iter()
+            .zip(split_names(&bodies, prefix, options.stable_names))
+        {
+            out.push_str(&documented(doc_str(&content[fence.range.clone()]), &name));
+        }
+        out.push_str(&includes);
+        return Ok(out);
+    }
+    if options.from_rust_all.is_some() {
+        // `native` is rejected before so the content (the rust source) is always there.
+        let source = content.as_ref().map(|c| c.as_str()).unwrap_or("");
+        let prefix = match test_name {
+            Some(t) => t.clone(),
+            None => stem_ident(file_path),
+        };
+        let mut names: Vec<String> = Vec::new();
+        for (item, markdown) in extract_item_docs(source) {
+            let base = format!("{}_{}", prefix, sanitize_ident(&item).to_lowercase());
+            let mut name = base.clone();
+            let mut index = 2;
+            while names.contains(&name) {
+                name = format!("{}_{}", base, index);
+                index += 1;
+            }
+            out.push_str(&documented(doc_str(&markdown), &name));
+            names.push(name);
+        }
+        out.push_str(&includes);
+        return Ok(out);
+    }
+    if let Some(ref name) = options.build_up {
+        if test_name.is_some() || !variants.is_empty() {
+            return Err(Error::call_site(
+                "`build_up` cannot be used with a test name".to_owned(),
+            ));
+        }
+        // `native` is rejected before so the content is always there.
+        let content = content.as_ref().map(|c| c.as_str()).unwrap_or("");
+        out.push_str(&documented(doc_str(&build_up(content)), name));
+        out.push_str(&includes);
+        return Ok(out);
+    }
+    for (modifier, name) in variants {
+        out.push_str(&documented(
+            doc_for(modifier.as_ref().map(|m| m.as_str())),
+            name,
+        ));
+    }
+    for (predicate, name) in &options.cfg_variants {
+        out.push_str(&format!("#[cfg({})]\n", predicate));
+        out.push_str(&documented(doc_for(None), name));
+    }
+    match test_name {
+        Some(t) => out.push_str(&documented(doc_for(None), t)),
+        None if variants.is_empty() && options.cfg_variants.is_empty() => {
+            out.push_str(&documented(doc_for(None), ""))
+        }
+        None => {}
+    }
+    out.push_str(&includes);
+    Ok(out)
+}
+
+/// Parses a `[modifier => name]` variant of `doctest`. `run` means the code blocks are kept as is
+/// so it returns `None` for it.
+fn parse_variant(group: &Group) -> Result<(Option<String>, String), Error> {
+    let tokens = group.stream().into_iter().collect::<Vec<_>>();
+    match tokens.as_slice() {
+        [TokenTree::Ident(modifier), TokenTree::Punct(eq), TokenTree::Punct(gt), TokenTree::Ident(name)]
+            if eq.as_char() == '=' && gt.as_char() == '>' =>
+        {
+            let modifier_s = modifier.to_string();
+            let modifier_s = match modifier_s.as_str() {
+                "run" => None,
+                "no_run" | "ignore" | "should_panic" | "compile_fail" => Some(modifier_s),
+                m => {
+                    return Err(Error::new(
+                        modifier.span(),
+                        format!(
+                            "Unknown code block modifier `{}` (expected `run`, `no_run`, \
+                             `ignore`, `should_panic` or `compile_fail`)",
+                            m
+                        ),
+                    ))
+                }
+            };
+            let name_s = name.to_string();
+            if is_keyword(&name_s) {
+                return Err(Error::new(
+                    name.span(),
+                    format!("expected an identifier, found keyword `{}`", name_s),
+                ));
+            }
+            Ok((modifier_s, name_s))
+        }
+        _ => Err(Error::new(
+            group.span(),
+            "expected `[modifier => name]`".to_owned(),
+        )),
+    }
+}
+
+/// Groups the tests of multiple files into one module, each file getting its own child module:
+///
+/// ```edition2018,no_run
+/// doc_comment::doctest_all! {
+///     in docs_tests;
+///     "../README.md" => readme,
+///     "../tests/fixtures/tabs.md" => tabs,
+/// }
+/// # fn main() {}
+/// ```
+///
+/// It's the equivalent of:
+///
+/// ```edition2018,no_run
+/// mod docs_tests {
+///     doc_comment::doctest! { "../README.md", readme }
+///     doc_comment::doctest! { "../tests/fixtures/tabs.md", tabs }
 /// }
+/// # fn main() {}
 /// ```
+#[proc_macro]
+pub fn doctest_all(item: TokenStream) -> TokenStream {
+    match expand_doctest_all(item) {
+        Ok(tokens) => tokens,
+        Err(e) => e.into_compile_error(),
+    }
+}
+
+/// Returns the next token if it's an identifier which isn't a keyword.
+fn expect_name(parts: &mut Peekable<ProcIter>, what: &str) -> Result<String, Error> {
+    match parts.next() {
+        Some(TokenTree::Ident(ref i)) if !is_keyword(&i.to_string()) => Ok(i.to_string()),
+        Some(t) => Err(Error::new(
+            t.span(),
+            format!("expected {}, found `{}`", what, t),
+        )),
+        None => Err(Error::call_site(format!("expected {}", what))),
+    }
+}
+
+fn expect_punct(parts: &mut Peekable<ProcIter>, c: char) -> Result<(), Error> {
+    match parts.next() {
+        Some(TokenTree::Punct(ref p)) if p.as_char() == c => Ok(()),
+        Some(t) => Err(Error::new(
+            t.span(),
+            format!("expected `{}`, found `{}`", c, t),
+        )),
+        None => Err(Error::call_site(format!("expected `{}`", c))),
+    }
+}
+
+fn expand_doctest_all(item: TokenStream) -> Result<TokenStream, Error> {
+    let mut parts = item.into_iter().peekable();
+    match parts.next() {
+        Some(TokenTree::Ident(ref i)) if i.to_string() == "in" => {}
+        Some(t) => {
+            return Err(Error::new(
+                t.span(),
+                format!("expected `in <module>;`, found `{}`", t),
+            ))
+        }
+        None => {
+            return Err(Error::call_site(
+                "doctest_all expects `in <module>;` followed by `\"path\" => name` pairs"
+                    .to_owned(),
+            ))
+        }
+    }
+    let module = expect_name(&mut parts, "a module name")?;
+    expect_punct(&mut parts, ';')?;
+
+    let options = DoctestOptions::default();
+    let mut out = String::new();
+    while let Some(t) = parts.next() {
+        let path = match literal_value(&t)? {
+            Some(path) => path,
+            None => {
+                return Err(Error::new(
+                    t.span(),
+                    format!("expected a file path, found `{}`", t),
+                ))
+            }
+        };
+        expect_punct(&mut parts, '=')?;
+        expect_punct(&mut parts, '>')?;
+        let name = expect_name(&mut parts, "an identifier")?;
+        out.push_str(&expand_file(
+            Path::new(&path),
+            t.span(),
+            Some(&name),
+            &[],
+            &options,
+        )?);
+        match parts.next() {
+            Some(TokenTree::Punct(ref p)) if p.as_char() == ',' => {}
+            Some(t) => return Err(Error::new(t.span(), format!("expected `,`, found `{}`", t))),
+            None => break,
+        }
+    }
+    parse_generated(&format!("mod {} {{ {} }}", module, out))
+}
+
+/// Documents the annotated item with a file, accepting the same options as `doctest`. The code
+/// block modifiers (`no_run`, `ignore`, `should_panic` and `compile_fail`) can be given directly
+/// and are added to all the rust code blocks, like with `block_attrs`:
 ///
-/// Unfortunately, due to current rust limitations, you can't use it as an inner attribute yet (you
-/// can check the issue [here](https://github.com/rust-lang/rust/issues/41430)):
-///
-/// ```ignore
-/// pub mod bar {
-///     #![dc("yolo")] // not working!
-/// }
+/// ```edition2018,no_run
+/// #[doc_comment::from_file("../tests/fixtures/body.md", no_run, edition2021)]
+/// pub mod guide {}
+/// # fn main() {}
 /// ```
+///
+/// Since the documentation is on your item, the options generating other items or test names
+/// (like `files`, `split` or `build_up`) cannot be used.
 #[proc_macro_attribute]
-pub fn doc_comment(attrs: TokenStream, item: TokenStream) -> TokenStream {
-    let mut includes = String::new();
-    // let is_inner = check_if_is_inner(&mut item);
-    let is_inner = false;
-    #[cfg(not(feature = "debug"))]
-    {
-       let mut item = item.into_iter().peekable();
-        let attr = TokenStream::from_str(&parse_attr(attrs, &mut includes, is_inner))
-            .unwrap()
-            .into_iter();
-        if !is_inner {
-            let item = TokenStream::from_str(&parse_item(item, &mut includes))
-                .unwrap()
-                .into_iter();
-            let includes: ProcIter = TokenStream::from_str(&includes).unwrap().into_iter();
-            TokenStream::from_iter(attr.chain(item).chain(includes))
-        } else {
-            // This whole thing is just a non-working hack because when you're writing an inner
-            // attribute with this proc-macro, it strangely returns an anonymous module wrapping
-            // everything inside the current scope. But if you try to insert or modify anything, it
-            // doesn't work so for now, it's completely useless...
-            loop {
-                match item.next() {
-                    Some(TokenTree::Group(g)) => {
-                        let tokens: ProcIter = g.stream().into_iter();
-                        let includes: ProcIter = TokenStream::from_str(&includes).unwrap().into_iter();
+pub fn from_file(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    match expand_from_file(attrs) {
+        Ok(attr) => TokenStream::from_iter(attr.into_iter().chain(item)),
+        // We keep the item so the error doesn't cascade into "unresolved item" ones.
+        Err(e) => TokenStream::from_iter(e.into_compile_error().into_iter().chain(item)),
+    }
+}
 
-                        return TokenStream::from_iter(attr.chain(tokens).chain(includes));
-                    }
-                    Some(_) => {}
-                    None => {
-                        let includes: ProcIter = TokenStream::from_str(&includes).unwrap().into_iter();
-                        // Weird case... It would meant that we can't find a "TokenTree::Group" where
-                        // the inner attribute would be located...
-                        return TokenStream::from_iter(attr.chain(includes));
-                    }
-                }
+fn expand_from_file(attrs: TokenStream) -> Result<TokenStream, Error> {
+    let mut parts = attrs.into_iter().peekable();
+    let (path, span) = match parts.next() {
+        Some(t) => match literal_value(&t)? {
+            Some(path) => (path, t.span()),
+            None => {
+                return Err(Error::new(
+                    t.span(),
+                    format!(
+                        "First parameter of from_file should be a string literal, found `{}`",
+                        t
+                    ),
+                ))
+            }
+        },
+        None => return Err(Error::call_site("from_file expects a file path".to_owned())),
+    };
+    let mut options = DoctestOptions::default();
+    while let Some(t) = parts.next() {
+        let i = match t {
+            TokenTree::Punct(ref p) if p.as_char() == ',' => continue,
+            TokenTree::Ident(i) => i,
+            t => return Err(Error::new(t.span(), format!("Unexpected token `{}`", t))),
+        };
+        let i_s = i.to_string();
+        match parts.peek() {
+            Some(TokenTree::Punct(p)) if p.as_char() == '=' => {
+                parts.next();
+                let value = take_value(&mut parts);
+                options.set(&i, value)?;
             }
+            _ => match i_s.as_str() {
+                "no_run" | "ignore" | "should_panic" | "compile_fail" => {
+                    options.block_attrs.push(i_s)
+                }
+                _ if options.set_flag(&i_s) => {}
+                _ => options.unknown.push(Error::new(
+                    i.span(),
+                    format!("Unknown from_file flag `{}`", i_s),
+                )),
+            },
         }
     }
-    #[cfg(feature = "debug")]
+    if !options.allow_unknown && !options.unknown.is_empty() {
+        return Err(options.unknown.remove(0));
+    }
+    if !options.files.is_empty()
+        || !options.join.is_empty()
+        || options.base.is_some()
+        || options.split
+        || options.build_up.is_some()
+        || options.from_rust_all.is_some()
+        || options.template.is_some()
     {
-        let attr = parse_attr(attrs, &mut includes, is_inner);
-        let it = parse_item(item.into_iter().peekable(), &mut includes);
-        panic!("{}\n{}\n{}", attr, it, includes);
+        return Err(Error::call_site(
+            "`from_file` cannot be used with `files`, `join`, `base`, `split`, `build_up`, \
+             `from_rust_all` or `template`"
+                .to_owned(),
+        ));
     }
+    options.attach = true;
+    options.attribute = true;
+    let path = options.path(&path);
+    parse_generated(&expand_file(&path, span, None, &[], &options)?)
 }
 
-/// This proc macro provides a simpler way to test an outer markdown file.
-///
-/// # Example
+/// Expands to a `&[&str]` of the files matching the given pattern, sorted. Only the file name can
+/// contain wildcards: `*` matches any number of characters and `?` matches one. It doesn't
+/// generate any documentation, it's meant for your own checks on the documentation files:
 ///
 /// ```edition2018,no_run
-/// doc_comment::doctest! { "../README.md" }
-/// // It is the equivalent of:
-/// #[doc_comment::doc_comment(include_str!("../README.md"))]
-/// extern {}
+/// const DOCS: &[&str] = doc_comment::list_files!("../tests/fixtures/join/*tro.md");
 ///
-/// // If you want to have a name for your tests:
-/// doc_comment::doctest! { "../README.md", another }
+/// #[test]
+/// fn all_docs_tested() {
+///     assert_eq!(DOCS, &["../tests/fixtures/join/intro.md", "../tests/fixtures/join/outro.md"]);
+/// }
 /// # fn main() {}
 /// ```
+///
+/// The paths are relative to the same directory as for `doctest`. Since the compiler can only
+/// track files, adding a file to the directory doesn't trigger a rebuild.
 #[proc_macro]
-pub fn doctest(item: TokenStream) -> TokenStream {
-    let mut parts = item.into_iter();
-    let mut file_path = None;
-    let mut test_name = None;
+pub fn list_files(item: TokenStream) -> TokenStream {
+    match expand_list_files(item) {
+        Ok(tokens) => tokens,
+        Err(e) => e.into_compile_error(),
+    }
+}
 
-    loop {
-        match parts.next() {
-            Some(TokenTree::Literal(l)) => {
-                let l_s = l.to_string();
-                if !l_s.starts_with('"') {
-                    panic!(
-                        "First parameter of doctest should be a string literal, found `{}`",
-                        l_s
-                    );
-                } else if file_path.is_some() {
-                    panic!(
-                        "Second parameter of doctest should be a ident, found a literal: `{}`",
-                        l_s
-                    );
-                }
-                file_path = Some(l_s[1..l_s.len() - 1].to_owned());
-            }
-            Some(TokenTree::Punct(ref p)) if p.to_string() == "," => {}
-            Some(TokenTree::Ident(i)) => {
-                let i_s = i.to_string();
-                if file_path.is_none() {
-                    panic!(
-                        "First parameter of doctest should be a string literal, found ident `{}`",
-                        i_s
-                    );
-                }
-                test_name = Some(i_s);
+/// Returns `true` if `name` matches `pattern`, where `*` matches any number of characters and `?`
+/// exactly one.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    // Position of the last `*` in `pattern` and of the `name` character it's matched up to.
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // The `*` takes one more character.
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn expand_list_files(item: TokenStream) -> Result<TokenStream, Error> {
+    let mut parts = item.into_iter();
+    let (pattern, span) = match parts.next() {
+        Some(t) => match literal_value(&t)? {
+            Some(pattern) => (pattern, t.span()),
+            None => {
+                return Err(Error::new(
+                    t.span(),
+                    format!("expected a string literal, found `{}`", t),
+                ))
             }
-            Some(t) => panic!("Unexpected token `{}`", t),
-            None => break,
+        },
+        None => {
+            return Err(Error::call_site(
+                "list_files expects a path pattern like `\"docs/*.md\"`".to_owned(),
+            ))
         }
+    };
+    match parts.next() {
+        Some(TokenTree::Punct(ref p)) if p.as_char() == ',' => {}
+        None => {}
+        Some(t) => return Err(Error::new(t.span(), format!("unexpected `{}`", t))),
     }
-    if file_path.is_none() && test_name.is_none() {
-        panic!("doctest expects at least one parameter");
+    if let Some(t) = parts.next() {
+        return Err(Error::new(t.span(), format!("unexpected `{}`", t)));
     }
-    let mut includes = String::new();
-    let content = include_file(
-        "include_str",
-        &Path::new(file_path.as_ref().unwrap()),
-        &mut includes,
-    );
-    let item = match test_name {
-        Some(t) => format!("mod {} {{}}", t),
-        None => "extern {}".to_owned(),
+    let (dir, file_pattern) = match pattern.rfind('/') {
+        Some(pos) => (&pattern[..pos + 1], &pattern[pos + 1..]),
+        None => ("", pattern.as_str()),
     };
-    format!("#[doc = \"{}\"]\n{}\n{}", content, item, includes)
-        .parse()
-        .unwrap()
+    if dir.contains(|c| c == '*' || c == '?') {
+        return Err(Error::new(
+            span,
+            "wildcards are only supported in the file name".to_owned(),
+        ));
+    }
+    let full_dir = resolve_path(Path::new(if dir.is_empty() { "." } else { dir }));
+    let entries = fs::read_dir(&full_dir).map_err(|e| {
+        Error::new(
+            span,
+            format!("Failed to read `{}`: {}", full_dir.display(), e),
+        )
+    })?;
+    let mut paths = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().to_str().map(|name| name.to_owned()))
+        .filter(|name| wildcard_match(file_pattern, name))
+        .map(|name| format!("{}{}", dir, name))
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        return Err(Error::new(
+            span,
+            format!("`{}` doesn't match any file", pattern),
+        ));
+    }
+    paths.sort();
+    let paths = paths
+        .iter()
+        .map(|p| format!("\"{}\"", escape_content(p)))
+        .collect::<Vec<_>>();
+    parse_generated(&format!("&[{}]", paths.join(", ")))
 }
 
 // Maybe if some day the proc-macros are supported at the crate level?
@@ -484,3 +4866,634 @@ pub fn doctest(item: TokenStream) -> TokenStream {
 //             .chain(TokenStream::from_str(&includes).unwrap().into_iter())
 //     )
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        add_block_attrs, add_extern_crate, add_footer, add_modifier_to, add_no_std,
+        add_source_markers, build_up, check_tokens, dedent, doc_cfg_attr, end_with_newline,
+        escape_content, expand_home, extract_doc_comments, extract_item_docs, extract_regions,
+        fence_modes, first_difference, fnv1a, hide_lines, hide_tracking, item_name, keep_lines,
+        mixed_indent_line, not_found_message, parse_line_list, parse_ranges, relative_links,
+        set_edition, split_names, start_at_column_zero, stem_ident, strip_comments,
+        strip_components, strip_front_matter, strip_hidden_lines, track_file, unescape,
+        unhide_lines, untest_quoted_fences, uses_crate, wildcard_match, wrap_in_fence, wrap_main,
+        DoctestOptions,
+    };
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn modifier_to() {
+        assert_eq!(
+            add_modifier_to(
+                "```rust,should_panic\nx\n```\n```rust\ny\n```\n```should_panic,no_run\nz\n```\n\
+                 ```text,should_panic\nw\n```\n",
+                "no_run",
+                "should_panic",
+            ),
+            "```rust,should_panic,no_run\nx\n```\n```rust\ny\n```\n```should_panic,no_run\nz\n```\n\
+             ```text,should_panic\nw\n```\n",
+        );
+    }
+
+    #[test]
+    fn source_markers() {
+        let content = "# Guide\n\n```rust\nlet x = 1;\n```\n\n```text\nnot rust\n```\n```\n```";
+        let marked = add_source_markers(content, Path::new("guide.md"));
+        assert_eq!(
+            marked,
+            "# Guide\n\n```rust\n# // source: guide.md:4\nlet x = 1;\n```\n\n```text\nnot rust\n\
+             ```\n```\n# // source: guide.md:11\n```",
+        );
+        // The markers are hidden.
+        assert_eq!(strip_hidden_lines(&marked), strip_hidden_lines(content),);
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(
+            parse_ranges("# Generated\n1..3\n\n 5..=6 \n"),
+            Ok(vec![(1, 3), (5, 7)]),
+        );
+        for invalid in &["1", "a..3", "0..2", "3..1", "1..b", "1..=", "..2"] {
+            assert!(parse_ranges(invalid).is_err(), "{}", invalid);
+        }
+        assert_eq!(
+            keep_lines("a\nb\nc\nd\ne\nf", &[(2, 3), (5, 7)]),
+            "\nb\n\n\ne\nf",
+        );
+    }
+
+    #[test]
+    fn components() {
+        let path = Path::new("../docs/guide.md");
+        assert_eq!(strip_components(path, 0), "../docs/guide.md");
+        assert_eq!(strip_components(path, 1), "docs/guide.md");
+        assert_eq!(strip_components(path, 2), "guide.md");
+        assert_eq!(strip_components(path, 10), "guide.md");
+    }
+
+    #[test]
+    fn front_matter() {
+        let content = "---\ntitle: Guide\ntags: [a, b]\n---\n# Guide\n\n```rust\nlet x = 1;\n```\n";
+        assert_eq!(
+            strip_front_matter(content, false),
+            "# Guide\n\n```rust\nlet x = 1;\n```\n",
+        );
+        let stripped = strip_front_matter(content, true);
+        assert_eq!(stripped.lines().count(), content.lines().count());
+        assert_eq!(stripped.lines().nth(7), Some("let x = 1;"));
+        assert_eq!(strip_front_matter("+++\na = 1\n+++\nb", false), "b");
+        // Not at the start or not closed.
+        assert_eq!(
+            strip_front_matter("a\n---\nb\n---\n", false),
+            "a\n---\nb\n---\n"
+        );
+        assert_eq!(strip_front_matter("---\nb\n", false), "---\nb\n");
+    }
+
+    #[test]
+    fn doc_comments_preserve_lines() {
+        let source = "//! # Title\n\nuse std::fmt;\n\n/// Doc.\nfn f() {}\n";
+        assert_eq!(extract_doc_comments(source, false), "# Title\nDoc.\n");
+        assert_eq!(
+            extract_doc_comments(source, true),
+            "# Title\n\n\n\nDoc.\n\n"
+        );
+    }
+
+    #[test]
+    fn crate_uses() {
+        assert!(uses_crate("use old_crate::Thing;", "old_crate"));
+        assert!(uses_crate("let x = ::old_crate::f();", "old_crate"));
+        assert!(uses_crate("extern crate old_crate;", "old_crate"));
+        assert!(uses_crate("extern crate old_crate as new;", "old_crate"));
+        assert!(!uses_crate("use my_old_crate::Thing;", "old_crate"));
+        assert!(!uses_crate("extern crate old_crate2;", "old_crate"));
+        assert!(!uses_crate("let old_crate = 1;", "old_crate"));
+    }
+
+    #[test]
+    fn build_up_blocks() {
+        let content = "# Step 1\n\n```\nstruct Point(i32);\n```\n\n```text\nignored\n```\n\n\
+                       ```compile_fail\nlet x: u8 = \"\";\n```\n\n```rust,no_run\nlet p = Point(1);\n```\n";
+        assert_eq!(
+            build_up(content),
+            "```rust,no_run\nstruct Point(i32);\n\nlet p = Point(1);\n```\n",
+        );
+        assert_eq!(build_up("no code\n"), "```rust\n```\n");
+    }
+
+    #[test]
+    fn split_module_names() {
+        let bodies = ["let a = 1;\n", "let b = 2;\n", "let a = 1;\n"];
+        assert_eq!(
+            split_names(&bodies, "block", false),
+            vec!["block_1", "block_2", "block_3"],
+        );
+        let names = split_names(&bodies, "readme", true);
+        assert_eq!(names[2], format!("{}_2", names[0]));
+        assert!(names[0].starts_with("readme_") && names[0].len() == "readme_".len() + 6);
+        // Reordering and inserting code blocks doesn't change the names.
+        let reordered = ["let c = 3;\n", "let b = 2;\n", "let a = 1;\n"];
+        let new_names = split_names(&reordered, "readme", true);
+        assert_eq!(new_names[1], names[1]);
+        assert_eq!(new_names[2], names[0]);
+        assert_eq!(fnv1a(""), 0x811c_9dc5);
+        assert_eq!(fnv1a("a"), 0xe40c_292c);
+    }
+
+    #[test]
+    fn trailing_newline() {
+        assert_eq!(end_with_newline("```\na\n```"), "```\na\n```\n");
+        assert_eq!(end_with_newline("a\n\n\r\n"), "a\n");
+        assert_eq!(end_with_newline("a\n"), "a\n");
+        assert_eq!(end_with_newline("\n\n"), "");
+        assert_eq!(
+            start_at_column_zero("    Intro.\n    more"),
+            "Intro.\n    more"
+        );
+        assert_eq!(start_at_column_zero("\t\n  a\n  b"), "a\n  b");
+        assert_eq!(start_at_column_zero("\r\n\n    Intro.\n"), "Intro.\n");
+    }
+
+    #[test]
+    fn doc_cfg() {
+        assert_eq!(
+            doc_cfg_attr("feature = \"x\""),
+            "#[cfg_attr(docsrs, doc(cfg(feature = \"x\")))]",
+        );
+    }
+
+    #[test]
+    fn hidden_lines() {
+        assert_eq!(
+            parse_line_list("1..3, 5,7..=8,"),
+            Ok(vec![(1, 3), (5, 6), (7, 9)])
+        );
+        assert!(parse_line_list("0").is_err());
+        assert!(parse_line_list("3..1").is_err());
+        assert!(parse_line_list("a").is_err());
+        assert_eq!(
+            hide_lines("use a;\n\nfn main() {\n    b();\n}\n", &[(1, 3), (5, 6)]),
+            "# use a;\n#\nfn main() {\n    b();\n# }\n",
+        );
+    }
+
+    #[test]
+    fn fence_wrapping() {
+        assert_eq!(wrap_in_fence("a\n", "rust"), "```rust\na\n```\n");
+        assert_eq!(wrap_in_fence("a", "text"), "```text\na\n```\n");
+        assert_eq!(
+            wrap_in_fence("/// ```\n/// b\n/// ````\n", "rust"),
+            "```rust\n/// ```\n/// b\n/// ````\n```\n",
+        );
+        assert_eq!(wrap_in_fence("````\n", ""), "`````\n````\n`````\n");
+    }
+
+    #[test]
+    fn extern_crate() {
+        assert_eq!(
+            add_extern_crate("use foo::Bar;\n", "foo"),
+            "# extern crate foo;\nuse foo::Bar;\n",
+        );
+        assert_eq!(
+            add_extern_crate("# #[macro_use]\n# extern crate foo;\n", "foo"),
+            "# #[macro_use]\n# extern crate foo;\n",
+        );
+        assert_eq!(
+            add_extern_crate("extern crate foo2;\n", "foo"),
+            "# extern crate foo;\nextern crate foo2;\n",
+        );
+    }
+
+    #[test]
+    fn modes() {
+        assert_eq!(
+            fence_modes(include_str!("../tests/fixtures/modes.md")),
+            vec![
+                "  line 3: run",
+                "  line 7 (`rust,should_panic`): run, must panic",
+                "  line 11 (`no_run`): compiled only",
+                "  line 15 (`compile_fail`): must fail to compile",
+                "  line 19 (`ignore`): ignored",
+                "  line 23 (`text`): not tested (not rust)",
+            ],
+        );
+        assert_eq!(fence_modes("text\n"), vec!["  no code blocks"]);
+    }
+
+    #[test]
+    fn holder_attrs() {
+        let mut options = DoctestOptions::default();
+        assert_eq!(options.holder_attrs(), "");
+        options.set_flag("hidden");
+        assert_eq!(options.holder_attrs(), "#[doc(hidden)]\n");
+        options.set_flag("cfg_doctest");
+        assert_eq!(options.holder_attrs(), "#[cfg(doctest)]\n#[doc(hidden)]\n");
+    }
+
+    #[test]
+    fn regions() {
+        let content = "intro\n<!-- doctest:start -->\none\n<!-- doctest:end -->\nskipped\n\
+                       <!--doctest:start-->\ntwo\n<!-- doctest:end -->\n<!-- other:start -->\n";
+        assert_eq!(
+            extract_regions(content, "doctest"),
+            Ok("one\n\ntwo\n".to_owned())
+        );
+        assert!(extract_regions(content, "other").is_err());
+        assert!(extract_regions("<!-- doctest:end -->\n", "doctest").is_err());
+        assert!(extract_regions(
+            "<!-- doctest:start -->\n<!-- doctest:start -->\n",
+            "doctest"
+        )
+        .is_err());
+        assert_eq!(
+            extract_regions("<!-- doctests:start -->\n", "doctest"),
+            Ok(String::new())
+        );
+    }
+
+    #[test]
+    fn item_docs() {
+        let source = "//! Crate docs.\n\n/// A struct.\n#[derive(Debug)]\npub struct Point<T>(T);\n\n\
+                      impl<T> Point<T> {\n    /// Builds one.\n    ///\n    /// ```\n    /// ok\n    \
+                      /// ```\n    pub(crate) const fn new(x: T) -> Self { Point(x) }\n}\n\
+                      /// Not an item.\nlet x = 1;\n";
+        assert_eq!(
+            extract_item_docs(source),
+            vec![
+                ("crate".to_owned(), "Crate docs.\n".to_owned()),
+                ("Point".to_owned(), "A struct.\n".to_owned()),
+                ("new".to_owned(), "Builds one.\n\n```\nok\n```\n".to_owned()),
+                ("item".to_owned(), "Not an item.\n".to_owned()),
+            ],
+        );
+        assert_eq!(
+            item_name("pub static mut COUNTER: u8 = 0;"),
+            Some("COUNTER".to_owned())
+        );
+        assert_eq!(
+            item_name("unsafe impl Send for X {}"),
+            Some("impl".to_owned())
+        );
+        assert_eq!(item_name("macro_rules! twice {"), Some("twice".to_owned()));
+        assert_eq!(
+            item_name("pub extern \"C\" fn ffi() {}"),
+            Some("ffi".to_owned())
+        );
+    }
+
+    #[test]
+    fn footer() {
+        assert_eq!(
+            add_footer("```rust\nlet x = 1;\n```\n\n", "Tested."),
+            "```rust\nlet x = 1;\n```\n\nTested.\n"
+        );
+        assert_eq!(add_footer("", "Tested."), "Tested.\n");
+        assert_eq!(
+            add_footer("~~~~\nlet x = 1;\n", "Tested."),
+            "~~~~\nlet x = 1;\n~~~~\n\nTested.\n"
+        );
+    }
+
+    #[test]
+    fn quoted_fences() {
+        assert_eq!(
+            untest_quoted_fences("> ```\n> x\n> ```\n> ```rust,no_run\n> ```\n> ~~~sh\n> ~~~\n"),
+            "> ```text\n> x\n> ```\n> ```text\n> ```\n> ~~~sh\n> ~~~\n",
+        );
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(
+            check_tokens(
+                "let s = \"(\\\"\";\nlet r = r#\"\"[\"#;\nlet c = ['{', '\\''];\n\
+                 fn f<'a>(x: &'a u8) {} // (\n/* /* { */ */\nlet b = br\"}\";",
+                1
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check_tokens("fn f() {\n    vec![1, 2);\n}", 1),
+            Err((2, "mismatched `)` (`[` opened on line 2)".to_owned()))
+        );
+        assert_eq!(
+            check_tokens("{\n(\n)", 1),
+            Err((1, "unclosed `{`".to_owned()))
+        );
+        assert_eq!(check_tokens("}", 1), Err((1, "unexpected `}`".to_owned())));
+        assert_eq!(
+            check_tokens("let s = \"\nabc", 1),
+            Err((1, "unclosed string literal".to_owned()))
+        );
+        assert_eq!(
+            check_tokens("/* /* */", 1),
+            Err((1, "unclosed block comment".to_owned()))
+        );
+        assert_eq!(
+            check_tokens(
+                "let c = '\\\\'; assert_eq!(c, '\\\\');\nlet b = (b'\\\\', '\\'', b'\\'');",
+                1
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn tracked_paths() {
+        let mut includes = String::new();
+        track_file(
+            "include_str",
+            Path::new("C:\\Users\\My Name\\\"docs\".md"),
+            &mut includes,
+        );
+        assert_eq!(
+            includes,
+            "const _: &'static str = include_str!(\"C:\\\\Users\\\\My Name\\\\\\\"docs\\\".md\");"
+        );
+    }
+
+    #[test]
+    fn wrapped_main() {
+        assert_eq!(
+            wrap_main(
+                "let x: u8 = \"1\".parse()?;\n",
+                "Result<(), std::num::ParseIntError>"
+            ),
+            "# fn main() -> Result<(), std::num::ParseIntError> {\nlet x: u8 = \"1\".parse()?;\n\
+             # Ok(())\n# }\n"
+        );
+        assert_eq!(
+            wrap_main("let x = 1;\n# Ok(())\n", "std::io::Result<()>"),
+            "# fn main() -> std::io::Result<()> {\nlet x = 1;\n# Ok(())\n# }\n"
+        );
+        assert_eq!(
+            wrap_main("std::process::ExitCode::SUCCESS", "std::process::ExitCode"),
+            "# fn main() -> std::process::ExitCode {\nstd::process::ExitCode::SUCCESS\n# }\n"
+        );
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(wildcard_match("*.md", "guide.md"));
+        assert!(wildcard_match("*.md", ".md"));
+        assert!(wildcard_match("ch??-*.md", "ch01-intro.md"));
+        assert!(wildcard_match("a*b*c", "aXbYbZc"));
+        assert!(wildcard_match("guide.md", "guide.md"));
+        assert!(!wildcard_match("*.md", "guide.mdx"));
+        assert!(!wildcard_match("ch??-*.md", "ch1-intro.md"));
+        assert!(!wildcard_match("a*b*c", "aXbYbZ"));
+    }
+
+    #[cfg(feature = "manifest")]
+    #[test]
+    fn manifest() {
+        let manifest =
+            std::env::temp_dir().join(format!("doc-comment-{}.list", std::process::id()));
+        let _ = std::fs::remove_file(&manifest);
+        std::env::set_var("DOC_COMMENT_MANIFEST", &manifest);
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/body.md");
+        super::append_to_manifest(&fixture).unwrap();
+        super::append_to_manifest(&fixture.parent().unwrap().join("tabs.md")).unwrap();
+        std::env::remove_var("DOC_COMMENT_MANIFEST");
+        let listed = std::fs::read_to_string(&manifest).unwrap();
+        let _ = std::fs::remove_file(&manifest);
+        let fixture = std::fs::canonicalize(&fixture).unwrap();
+        assert_eq!(
+            listed,
+            format!(
+                "{}\n{}\n",
+                fixture.display(),
+                fixture.with_file_name("tabs.md").display()
+            )
+        );
+    }
+
+    #[test]
+    fn home_paths() {
+        let home = Some(PathBuf::from("/home/ci"));
+        assert_eq!(
+            expand_home(Path::new("~/shared-docs/common.md"), home.clone()),
+            Ok(PathBuf::from("/home/ci/shared-docs/common.md"))
+        );
+        assert_eq!(
+            expand_home(Path::new("docs/~/a.md"), home.clone()),
+            Ok(PathBuf::from("docs/~/a.md"))
+        );
+        assert_eq!(
+            expand_home(Path::new("~user/a.md"), home),
+            Ok(PathBuf::from("~user/a.md"))
+        );
+        assert!(expand_home(Path::new("~/a.md"), None).is_err());
+    }
+
+    #[test]
+    fn missing_files() {
+        let tried = [
+            PathBuf::from("/project/src/docs/guide.md"),
+            PathBuf::from("/project/docs/guide.md"),
+        ];
+        assert_eq!(
+            not_found_message(Path::new("docs/guide.md"), &tried),
+            "`docs/guide.md` doesn't exist, tried:\n  - `/project/src/docs/guide.md`\n  - \
+             `/project/docs/guide.md`"
+        );
+    }
+
+    #[test]
+    fn quiet_tracking() {
+        let mut includes = String::new();
+        track_file("include_str", Path::new("guide.md"), &mut includes);
+        let hidden = hide_tracking(&includes);
+        assert!(hidden.contains("mod doc_comment_file_tracking"));
+        assert!(hidden.contains("include_str!(\"guide.md\")"));
+        assert_eq!(hide_tracking(""), "");
+    }
+
+    #[test]
+    fn mixed_indent() {
+        assert_eq!(
+            mixed_indent_line("fn f() {\n\tlet a = 1;\n    a\n}\n"),
+            None
+        );
+        assert_eq!(mixed_indent_line("fn f() {\n\t let a = 1;\n}"), Some(1));
+        assert_eq!(mixed_indent_line("let s = \"a \t b\";\n  \t\n"), None);
+        assert_eq!(mixed_indent_line("\t \n \tlet a = 1;\n"), Some(1));
+    }
+
+    #[test]
+    fn stem_idents() {
+        assert_eq!(
+            stem_ident(Path::new("../docs/Quick-Start.md")),
+            "quick_start"
+        );
+        assert_eq!(stem_ident(Path::new("README")), "readme");
+        assert_eq!(stem_ident(Path::new("2021 notes.md")), "_2021_notes");
+    }
+
+    #[test]
+    fn dedented() {
+        assert_eq!(
+            dedent("Summary.\n\n        ```\n        let x = 1;\n            x\n        ```"),
+            "Summary.\n\n```\nlet x = 1;\n    x\n```",
+        );
+        assert_eq!(dedent("  \n    a\n   \n    b\n"), "\na\n\nb\n");
+        assert_eq!(dedent("a\nb"), "a\nb");
+    }
+
+    #[test]
+    fn links() {
+        let content = "See [setup](setup.md#install), [the API](<api docs.md> \"API\") and \
+                       [below](#usage).\n\
+                       [crates.io](https://crates.io), [root](/index.md), `[a](code.md)`\n\
+                       ```\n[b](fence.md)\n```\n\
+                       ![logo](img/logo.png?raw=true)\n";
+        assert_eq!(
+            relative_links(content),
+            vec![
+                (1, "setup.md".to_owned()),
+                (1, "api docs.md".to_owned()),
+                (6, "img/logo.png".to_owned()),
+            ],
+        );
+    }
+
+    #[test]
+    fn unhidden_lines() {
+        assert_eq!(
+            unhide_lines("# fn main() {\n#\nlet x = 1;\n    ##[allow(unused)]\n# }"),
+            "fn main() {\n\nlet x = 1;\n    #[allow(unused)]\n}",
+        );
+    }
+
+    #[test]
+    fn differences() {
+        assert_eq!(first_difference("a\nb\n", "a\nb\n"), None);
+        assert_eq!(first_difference("a\nb\n", "a\nb"), Some(3));
+        assert_eq!(first_difference("a\nb\n", "a\r\nb\n"), Some(1));
+        assert_eq!(first_difference("a\nb\nc\n", "a\nc\nc\n"), Some(2));
+        assert_eq!(first_difference("a\n", "a\nb\n"), Some(2));
+        assert_eq!(first_difference("", ""), None);
+    }
+
+    #[test]
+    fn no_std() {
+        assert_eq!(
+            add_no_std("let x = 1;"),
+            "# #![no_std]\n# extern crate alloc;\n# extern crate std;\n# fn main() {\nlet x = 1;\n# }\n",
+        );
+        assert_eq!(
+            add_no_std("fn main() {}\n"),
+            "# #![no_std]\n# extern crate alloc;\n# extern crate std;\nfn main() {}\n",
+        );
+        assert_eq!(add_no_std("#![no_std]\n"), "#![no_std]\n");
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(
+            strip_comments(
+                "// Setup.\nlet url = \"http://a\"; // The url.\n/// Docs.\nlet s = \"\\\"//\";\n  //\n"
+            ),
+            "let url = \"http://a\";\n/// Docs.\nlet s = \"\\\"//\";\n",
+        );
+        assert_eq!(strip_comments("//! Docs.\n//// Not docs.\n"), "//! Docs.\n");
+        assert_eq!(
+            strip_comments(
+                "let q = '\"'; let u = \"http://x\"; // c\nlet r = (r#\"\"//\"#, br\"\\\", '\\'', 'a); // c\n"
+            ),
+            "let q = '\"'; let u = \"http://x\";\nlet r = (r#\"\"//\"#, br\"\\\", '\\'', 'a);\n",
+        );
+    }
+
+    #[test]
+    fn edition() {
+        let content = "```\na\n```\n```rust,edition2015\nb\n```\n```text\nc\n```\n";
+        assert_eq!(
+            set_edition(content, "edition2021"),
+            "```edition2021\na\n```\n```rust,edition2015\nb\n```\n```text\nc\n```\n",
+        );
+    }
+
+    #[test]
+    fn block_attrs() {
+        assert_eq!(
+            add_block_attrs(
+                "```rust,no_run\nx\n```\n```\ny\n```\n```text\nz\n```\n",
+                &["should_panic", "no_run"],
+            ),
+            "```rust,no_run,should_panic\nx\n```\n```should_panic,no_run\ny\n```\n```text\nz\n```\n",
+        );
+    }
+
+    fn check_round_trip(s: &str) {
+        let escaped = escape_content(s);
+        assert_eq!(
+            unescape(&escaped).as_ref().map(|s| s.as_str()),
+            Some(s),
+            "{:?}",
+            escaped
+        );
+    }
+
+    #[test]
+    fn unescape_literals() {
+        assert_eq!(
+            unescape("a\\n\\t\\\\ \\x41\\u{e9}\\u{1F_980} \\\n   b"),
+            Some("a\n\t\\ A\u{e9}\u{1F980} b".to_owned()),
+        );
+        for invalid in &["\\x80", "\\x4", "\\u{110000}", "\\u41", "\\q", "\\"] {
+            assert_eq!(unescape(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn escape_round_trip() {
+        for s in &[
+            "",
+            "\"",
+            "\\",
+            "\\\"",
+            "\"\\",
+            "a \"quoted\" string",
+            "C:\\path\\to\\file",
+            "line\nother line\n",
+            "windows\r\nline endings\r\n",
+            "bare\rcarriage return",
+            "\ttabs\t",
+            "r#\"raw string\"#",
+            "unicode: é à ç ß 漢字",
+            "emoji: 🦀🎉",
+            "```rust\nlet x = \"\\n\";\n```\n",
+            "~~~\nfenced with tildes\n~~~",
+            "{} {{}} {:?}",
+        ] {
+            check_round_trip(s);
+        }
+    }
+
+    #[test]
+    fn escape_round_trip_random() {
+        const ALPHABET: &[char] = &[
+            'a', 'Z', '0', ' ', '"', '\\', '\'', '\n', '\r', '\t', '`', '~', '#', '{', '}', 'é',
+            '🦀',
+        ];
+        // A simple xorshift so the test is reproducible.
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..1000 {
+            let len = (next() % 32) as usize;
+            let s = (0..len)
+                .map(|_| ALPHABET[(next() % ALPHABET.len() as u64) as usize])
+                .collect::<String>();
+            check_round_trip(&s);
+        }
+    }
+}
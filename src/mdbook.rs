@@ -0,0 +1,127 @@
+//! Support of the mdBook `{{#include path}}` directives and `ANCHOR` comments.
+
+use fences::lines_with_endings;
+use std::ops::Range;
+
+/// A `{{#include path}}` (or `{{#include path:anchor}}`) directive.
+pub struct Include<'a> {
+    /// Range of the whole directive (braces included).
+    pub range: Range<usize>,
+    pub path: &'a str,
+    pub anchor: Option<&'a str>,
+}
+
+/// Returns the `{{#include ...}}` directives of `content`, in order.
+pub fn find_includes<'a>(content: &'a str) -> Vec<Include<'a>> {
+    let mut includes = Vec::new();
+    let mut pos = 0;
+    while let Some(start) = content[pos..].find("{{") {
+        let start = pos + start;
+        let end = match content[start..].find("}}") {
+            Some(end) => start + end + 2,
+            None => break,
+        };
+        let inner = content[start + 2..end - 2].trim();
+        pos = end;
+        if !inner.starts_with("#include") {
+            continue;
+        }
+        let rest = &inner["#include".len()..];
+        // Not `{{#includes x}}` and not without a path.
+        if !rest.starts_with(char::is_whitespace) || rest.trim().is_empty() {
+            continue;
+        }
+        let arg = rest.trim();
+        let (path, anchor) = match arg.find(':') {
+            Some(colon) => (arg[..colon].trim(), Some(arg[colon + 1..].trim())),
+            None => (arg, None),
+        };
+        includes.push(Include {
+            range: start..end,
+            path,
+            anchor,
+        });
+    }
+    includes
+}
+
+/// If `line` contains `<marker>: <name>`, returns `name`.
+fn anchor_name<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+    let pos = line.find(marker)?;
+    let rest = line[pos + marker.len()..].trim_start();
+    let end = rest
+        .find(|c: char| !c.is_alphanumeric() && c != '_' && c != '-')
+        .unwrap_or(rest.len());
+    if end == 0 {
+        None
+    } else {
+        Some(&rest[..end])
+    }
+}
+
+/// Returns the lines between `ANCHOR: <anchor>` and `ANCHOR_END: <anchor>`. Like mdBook, the lines
+/// of the other anchors inside are removed.
+pub fn extract_anchor(content: &str, anchor: &str) -> Result<String, String> {
+    let mut out = None;
+    for line in lines_with_endings(content) {
+        match out {
+            None => {
+                if anchor_name(line, "ANCHOR:") == Some(anchor) {
+                    out = Some(String::new());
+                }
+            }
+            Some(ref mut out) => {
+                if anchor_name(line, "ANCHOR_END:") == Some(anchor) {
+                    return Ok(out.clone());
+                }
+                if anchor_name(line, "ANCHOR:").is_none()
+                    && anchor_name(line, "ANCHOR_END:").is_none()
+                {
+                    out.push_str(line);
+                }
+            }
+        }
+    }
+    match out {
+        None => Err(format!("no `ANCHOR: {}`", anchor)),
+        Some(_) => Err(format!(
+            "`ANCHOR: {}` has no matching `ANCHOR_END: {}`",
+            anchor, anchor
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn includes() {
+        let content = "a {{#include one.md}} b\n{{ #include ../code.rs:main }}\n{{#includes x}}\
+                       {{#title x}}{{#include }}";
+        let includes = find_includes(content);
+        assert_eq!(includes.len(), 2);
+        assert_eq!(&content[includes[0].range.clone()], "{{#include one.md}}");
+        assert_eq!((includes[0].path, includes[0].anchor), ("one.md", None));
+        assert_eq!(
+            (includes[1].path, includes[1].anchor),
+            ("../code.rs", Some("main"))
+        );
+    }
+
+    #[test]
+    fn anchors() {
+        let content = "// ANCHOR: all\nuse std::fmt;\n// ANCHOR: main\nfn main() {}\n\
+                       // ANCHOR_END: main\n// ANCHOR_END: all\n// ANCHOR: open\n";
+        assert_eq!(
+            extract_anchor(content, "main"),
+            Ok("fn main() {}\n".to_owned())
+        );
+        assert_eq!(
+            extract_anchor(content, "all"),
+            Ok("use std::fmt;\nfn main() {}\n".to_owned())
+        );
+        assert!(extract_anchor(content, "mai").is_err());
+        assert!(extract_anchor(content, "open").is_err());
+    }
+}
@@ -0,0 +1,247 @@
+//! Minimal `{{key}}` templating for the `data` and `template` options of `doctest`.
+//!
+//! Only the top-level scalar values of the data file are supported: it's not meant to be a real
+//! TOML or JSON parser.
+
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Returns the top-level scalar values of a TOML file. Everything after the first table header
+/// is ignored, just like the values which aren't strings, numbers or booleans.
+pub fn parse_toml(content: &str) -> Result<HashMap<String, String>, String> {
+    let mut values = HashMap::new();
+    for (nb, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            break;
+        }
+        let eq = match line.find('=') {
+            Some(eq) => eq,
+            None => return Err(format!("line {}: expected `key = value`", nb + 1)),
+        };
+        let key = line[..eq].trim().trim_matches('"').to_owned();
+        let mut value = line[eq + 1..].trim().chars().peekable();
+        let value = match value.peek() {
+            Some('"') => {
+                value.next();
+                parse_string(&mut value, '"').map_err(|e| format!("line {}: {}", nb + 1, e))?
+            }
+            Some('\'') => {
+                let raw = line[eq + 1..].trim();
+                match raw[1..].find('\'') {
+                    Some(end) => raw[1..end + 1].to_owned(),
+                    None => return Err(format!("line {}: unclosed string", nb + 1)),
+                }
+            }
+            Some('[') | Some('{') | None => continue,
+            Some(_) => {
+                let raw = line[eq + 1..].trim();
+                raw.split('#').next().unwrap_or("").trim().to_owned()
+            }
+        };
+        values.insert(key, value);
+    }
+    Ok(values)
+}
+
+/// Returns the top-level scalar values of a JSON object. Arrays, objects and `null`s are ignored.
+pub fn parse_json(content: &str) -> Result<HashMap<String, String>, String> {
+    let mut values = HashMap::new();
+    let mut chars = content.chars().peekable();
+    skip_whitespace(&mut chars);
+    if chars.next() != Some('{') {
+        return Err("expected a JSON object".to_owned());
+    }
+    loop {
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            Some('}') => break,
+            Some('"') => {}
+            _ => return Err("expected a key".to_owned()),
+        }
+        let key = parse_string(&mut chars, '"')?;
+        skip_whitespace(&mut chars);
+        if chars.next() != Some(':') {
+            return Err(format!("expected `:` after `\"{}\"`", key));
+        }
+        skip_whitespace(&mut chars);
+        if let Some(value) = parse_json_value(&mut chars)? {
+            values.insert(key, value);
+        }
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            Some(',') => {}
+            Some('}') => break,
+            _ => return Err("expected `,` or `}`".to_owned()),
+        }
+    }
+    Ok(values)
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().map(|c| c.is_whitespace()) == Some(true) {
+        chars.next();
+    }
+}
+
+/// Parses a string whose opening quote has already been read.
+fn parse_string(chars: &mut Peekable<Chars>, quote: char) -> Result<String, String> {
+    let mut out = String::new();
+    loop {
+        match chars.next() {
+            None => return Err("unclosed string".to_owned()),
+            Some(c) if c == quote => return Ok(out),
+            Some('\\') => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('r') => out.push('\r'),
+                Some('u') => {
+                    let code = chars.by_ref().take(4).collect::<String>();
+                    match u32::from_str_radix(&code, 16)
+                        .ok()
+                        .and_then(std::char::from_u32)
+                    {
+                        Some(c) => out.push(c),
+                        None => return Err(format!("invalid escape `\\u{}`", code)),
+                    }
+                }
+                Some(c) => out.push(c),
+                None => return Err("unclosed string".to_owned()),
+            },
+            Some(c) => out.push(c),
+        }
+    }
+}
+
+/// Returns `None` for the values which aren't scalars.
+fn parse_json_value(chars: &mut Peekable<Chars>) -> Result<Option<String>, String> {
+    match chars.peek() {
+        Some('"') => {
+            chars.next();
+            parse_string(chars, '"').map(Some)
+        }
+        Some('[') | Some('{') => {
+            // Nested values are skipped.
+            let mut depth = 0;
+            while let Some(c) = chars.next() {
+                match c {
+                    '[' | '{' => depth += 1,
+                    ']' | '}' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Ok(None);
+                        }
+                    }
+                    '"' => {
+                        parse_string(chars, '"')?;
+                    }
+                    _ => {}
+                }
+            }
+            Err("unclosed array or object".to_owned())
+        }
+        _ => {
+            let mut raw = String::new();
+            while let Some(&c) = chars.peek() {
+                if c == ',' || c == '}' || c.is_whitespace() {
+                    break;
+                }
+                raw.push(c);
+                chars.next();
+            }
+            match raw.as_str() {
+                "" => Err("expected a value".to_owned()),
+                "null" => Ok(None),
+                _ => Ok(Some(raw)),
+            }
+        }
+    }
+}
+
+/// Replaces the `{{key}}` placeholders of `template` with the corresponding values. Returns the
+/// missing key as error. Braces around anything but an identifier (like in `format!("{{}}")`) are
+/// kept as is.
+pub fn fill(template: &str, values: &HashMap<String, String>) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end,
+            None => break,
+        };
+        let key = rest[start + 2..end].trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            out.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            continue;
+        }
+        match values.get(key) {
+            Some(value) => {
+                out.push_str(&rest[..start]);
+                out.push_str(value);
+            }
+            None => return Err(key.to_owned()),
+        }
+        rest = &rest[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toml() {
+        let values = parse_toml(
+            "# Options\nname = \"doc \\\"comment\\\"\"\npath = 'C:\\dir'\nretries = 3 # comment\n\
+             verbose = true\nlist = [1, 2]\n\n[table]\nignored = 1\n",
+        )
+        .unwrap();
+        assert_eq!(values.len(), 4);
+        assert_eq!(values["name"], "doc \"comment\"");
+        assert_eq!(values["path"], "C:\\dir");
+        assert_eq!(values["retries"], "3");
+        assert_eq!(values["verbose"], "true");
+        assert!(parse_toml("what").is_err());
+    }
+
+    #[test]
+    fn json() {
+        let values = parse_json(
+            "{\"name\": \"doc\\n\\u00e9\", \"retries\": 3, \"list\": [\"]\", {}], \"none\": null, \
+             \"nested\": {\"a\": 1}, \"ok\": false}",
+        )
+        .unwrap();
+        assert_eq!(values.len(), 3);
+        assert_eq!(values["name"], "doc\né");
+        assert_eq!(values["retries"], "3");
+        assert_eq!(values["ok"], "false");
+        assert!(parse_json("[]").is_err());
+        assert!(parse_json("{\"a\": 1").is_err());
+    }
+
+    #[test]
+    fn fill_placeholders() {
+        let mut values = HashMap::new();
+        values.insert("name".to_owned(), "retries".to_owned());
+        values.insert("default".to_owned(), "3".to_owned());
+        assert_eq!(
+            fill("`{{name}}` defaults to {{ default }}. {{ unclosed", &values),
+            Ok("`retries` defaults to 3. {{ unclosed".to_owned()),
+        );
+        assert_eq!(fill("{{missing}}", &values), Err("missing".to_owned()));
+        assert_eq!(
+            fill(
+                "format!(\"{{}} {{{{name}}}}\", {{name}}) {{ 1 + 1 }}",
+                &values
+            ),
+            Ok("format!(\"{{}} {{retries}}\", retries) {{ 1 + 1 }}".to_owned()),
+        );
+    }
+}
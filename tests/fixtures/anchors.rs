@@ -0,0 +1,15 @@
+// A source file containing examples.
+
+fn main() {
+    // ANCHOR: sum
+    let v = vec![1, 2, 3];
+    assert_eq!(v.iter().sum::<i32>(), 6);
+    // ANCHOR_END: sum
+
+    // ANCHOR: max
+    let v = vec![1, 5, 3];
+    assert_eq!(v.iter().max(), Some(&5));
+    // ANCHOR_END: max
+
+    // ANCHOR: unterminated
+}
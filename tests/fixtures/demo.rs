@@ -0,0 +1,22 @@
+//! A small demo program.
+//!
+//! ```rust
+//! assert_eq!(demo_double(3), 6);
+//! # fn demo_double(x: u32) -> u32 { x * 2 }
+//! ```
+
+// This comment isn't part of the documentation.
+
+/// Doubles `x`:
+///
+/// ```
+/// let x = 21;
+/// assert_eq!(x * 2, 42);
+/// ```
+fn double(x: u32) -> u32 {
+    x * 2
+}
+
+fn main() {
+    println!("{}", double(21));
+}
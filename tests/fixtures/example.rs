@@ -0,0 +1,7 @@
+use std::collections::HashMap;
+
+fn main() {
+    let mut map = HashMap::new();
+    map.insert("a", 1);
+    assert_eq!(map["a"], 1);
+}
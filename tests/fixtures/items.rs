@@ -0,0 +1,20 @@
+/// Adds one to `x`:
+///
+/// ```
+/// assert_eq!(1 + 1, 2);
+/// ```
+pub fn increment(x: u32) -> u32 {
+    x + 1
+}
+
+// Not documentation.
+
+/// A wrapper.
+///
+/// ```
+/// struct Wrapper(u8);
+/// let w = Wrapper(3);
+/// assert_eq!(w.0, 3);
+/// ```
+#[derive(Debug)]
+pub struct Wrapper(u8);
@@ -0,0 +1,9 @@
+// ANCHOR: all
+fn double(x: u32) -> u32 {
+    x * 2
+}
+
+// ANCHOR: main
+assert_eq!(double(2), 4);
+// ANCHOR_END: main
+// ANCHOR_END: all